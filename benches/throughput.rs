@@ -0,0 +1,80 @@
+//! Throughput of the tokenizer and the parser on large documents. Run with
+//! `cargo bench`; criterion compares each run with the previous one.
+extern crate criterion;
+extern crate mustdown;
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use mustdown::{Parser, Tokenizer};
+use std::io;
+
+const SECTION: &str = "# A header
+
+Some text with *emphasis*, **strong emphasis**, `code` and a [link](http://a.com).
+A second line with an ![image][logo] and snake_case_names.
+
+- a list item
+- another one with a [note link][link]
+  1. nested
+  2. ordered
+
+> a quote
+> over two lines
+
+```rust
+fn main() {
+    println!(\"hello\");
+}
+```
+
+Setext header
+-------------
+
+***
+
+[link]:http://b.com
+[logo]:http://c.com/logo.png
+";
+
+fn document(sections: usize) -> String {
+    SECTION.repeat(sections)
+}
+
+fn bench_tokenizer(c: &mut Criterion) {
+    let text = document(1000);
+    let mut group = c.benchmark_group("tokenizer");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_function("large document", |b| b.iter(|| Tokenizer::tokenizer(&text)));
+    group.finish();
+}
+
+fn bench_parser(c: &mut Criterion) {
+    let text = document(1000);
+    let mut group = c.benchmark_group("parser");
+    group.throughput(Throughput::Bytes(text.len() as u64));
+    group.bench_function("large document", |b| b.iter(|| Parser::new().parse(&text)));
+    group.bench_function("large document to io::sink", |b| {
+        b.iter(|| Parser::new().render_to(&text, io::sink()))
+    });
+    group.finish();
+}
+
+/// Texts that used to take quadratic time: every `[` looked for a `](` up to
+/// the end of the line.
+fn bench_pathological(c: &mut Criterion) {
+    let mut group = c.benchmark_group("pathological");
+    for (name, text) in [
+        ("20k [", "[".repeat(20_000)),
+        ("20k ![", "![".repeat(20_000)),
+    ] {
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(name, |b| b.iter(|| Parser::new().parse(&text)));
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(10);
+    targets = bench_tokenizer, bench_parser, bench_pathological
+}
+criterion_main!(benches);
@@ -0,0 +1,45 @@
+use std::error;
+use std::fmt;
+
+/// Errors returned by the fallible `try_*` and `render_*` functions of the
+/// tokenizer and the parser.
+///
+/// Tokenizing text can't produce the first three on its own. They come from
+/// tokens that were put together by hand in a way the tokenizer never would.
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    /// A list block was given something other than one of its list items.
+    NotAListItem,
+    /// A list item was added after something other than a list block.
+    NotAListBlock,
+    /// A `SpecialToken` or `DoubleSpecialToken` has a symbol with no HTML.
+    UnknownSymbol(char),
+    /// The `fmt::Write` that the output was going to, or a `Renderer`, failed.
+    Fmt(fmt::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NotAListItem => write!(f, "expected a list item of the same kind"),
+            Error::NotAListBlock => write!(f, "expected a list block to add the item to"),
+            Error::UnknownSymbol(c) => write!(f, "no HTML for inline symbol {:?}", c),
+            Error::Fmt(_) => write!(f, "failed to write the output"),
+        }
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(error: fmt::Error) -> Self {
+        Error::Fmt(error)
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Fmt(e) => Some(e),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,394 @@
+//! The document as a stream of events, for pull parsing.
+//!
+//! `Events` walks a `Document` and yields a `Start` and an `End` around the
+//! contents of every node with children, and a single event for every other
+//! node. Filter or change the events on the way, then hand them to
+//! `html::push_html` or to your own consumer.
+use super::node::{Block, Document, Inline, ListItem, TableCell, TableRow};
+use super::tokenizer::{Alignment, Span};
+
+use std::collections::HashMap;
+use std::vec;
+
+/// A node with children, given by `Event::Start` and `Event::End`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Paragraph,
+    /// A heading of the given level.
+    Heading(usize),
+    BlockQuote,
+    /// A code block with its info string, if any. Its text comes as a single
+    /// `Text` event.
+    CodeBlock(Option<String>),
+    /// A list, with the number of its first item if it is ordered.
+    List(Option<usize>),
+    Item,
+    Emphasis,
+    Strong,
+    Strikethrough,
+    /// A link to the URL. Its text comes as a single `Text` event.
+    Link(String),
+    /// An image of the URL. Its alt text comes as a single `Text` event.
+    Image(String),
+    /// A table, with the alignment of every column. It holds a `TableHead`,
+    /// then a `TableBody` unless it has no rows besides the header.
+    Table(Vec<Alignment>),
+    /// The header row of a table, whose cells are `HeaderCell`s.
+    TableHead,
+    TableBody,
+    TableRow,
+    /// A cell of the header row, with the alignment of its column.
+    HeaderCell(Alignment),
+    /// A cell of a body row, with the alignment of its column.
+    TableCell(Alignment),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Start(Tag),
+    End(Tag),
+    Text(String),
+    /// A code span.
+    Code(String),
+    SoftBreak,
+    HardBreak,
+    /// A horizontal rule.
+    Rule,
+    /// A `[label]: url` line, with its label and URL.
+    LinkDefinition(String, String),
+    /// The checkbox of a task list item, and whether it is checked. It comes
+    /// right after the `Start` of the item, or of the first paragraph of a
+    /// loose item.
+    TaskListMarker(bool),
+}
+
+/// What is left to walk at one level of the tree, and the tag to end it with.
+struct Frame {
+    nodes: Nodes,
+    end: Option<(Tag, Span)>,
+}
+
+enum Nodes {
+    /// Blocks, and whether they are in an item of a tight list.
+    Blocks(vec::IntoIter<Block>, bool),
+    /// List items, and whether their list is tight.
+    Items(vec::IntoIter<ListItem>, bool),
+    Inlines(vec::IntoIter<Inline>),
+    Events(vec::IntoIter<(Event, Span)>),
+    /// The head and the body of a table, each with its tag and span, and the
+    /// alignments of the columns.
+    Sections(vec::IntoIter<(Tag, Vec<TableRow>, Span)>, Vec<Alignment>),
+    /// Table rows, the alignments of their columns, and whether they are the
+    /// header row.
+    Rows(vec::IntoIter<TableRow>, Vec<Alignment>, bool),
+    /// Table cells with the alignments of their columns, and whether they are
+    /// header cells.
+    Cells(vec::IntoIter<(TableCell, Alignment)>, bool),
+}
+
+/// The next node of a frame, taken out of it.
+enum Next {
+    Block(Block, bool),
+    Item(ListItem, bool),
+    Inline(Inline),
+    Event(Event, Span),
+    Section(Tag, Vec<TableRow>, Span, Vec<Alignment>),
+    Row(TableRow, Vec<Alignment>, bool),
+    Cell(TableCell, Alignment, bool),
+    Done,
+}
+
+/// An iterator over the events of a document.
+///
+/// Reference links and images are resolved with `Document::resolve_references`
+/// first, so links whose label has no definition are given as literal `Text`.
+/// Paragraphs in the items of a tight list have no `Start` and `End`, only
+/// their contents.
+pub struct Events {
+    stack: Vec<Frame>,
+}
+
+impl Events {
+    pub fn new(document: Document) -> Self {
+        let definitions = document.definitions();
+        Events::with_definitions(document, definitions)
+    }
+
+    /// Walks `document`, resolving reference links and images with
+    /// `definitions` instead of the document's own link definitions.
+    pub fn with_definitions(mut document: Document, definitions: HashMap<String, String>) -> Self {
+        document.resolve_references(&definitions);
+        Events {
+            stack: vec![Frame {
+                nodes: Nodes::Blocks(document.children.into_iter(), false),
+                end: None,
+            }],
+        }
+    }
+
+    /// Turns the iterator into one that also yields the span of the source
+    /// text of every event. `End` has the span of the whole node.
+    pub fn into_spanned(mut self) -> impl Iterator<Item = (Event, Span)> {
+        std::iter::from_fn(move || self.next_spanned())
+    }
+
+    pub fn next_spanned(&mut self) -> Option<(Event, Span)> {
+        loop {
+            let frame = self.stack.last_mut()?;
+            let next = match &mut frame.nodes {
+                Nodes::Blocks(blocks, tight) => blocks.next().map(|b| Next::Block(b, *tight)),
+                Nodes::Items(items, tight) => items.next().map(|i| Next::Item(i, *tight)),
+                Nodes::Inlines(inlines) => inlines.next().map(Next::Inline),
+                Nodes::Events(events) => events.next().map(|(e, span)| Next::Event(e, span)),
+                Nodes::Sections(sections, alignments) => sections
+                    .next()
+                    .map(|(tag, rows, span)| Next::Section(tag, rows, span, alignments.clone())),
+                Nodes::Rows(rows, alignments, head) => rows
+                    .next()
+                    .map(|row| Next::Row(row, alignments.clone(), *head)),
+                Nodes::Cells(cells, head) => cells
+                    .next()
+                    .map(|(cell, alignment)| Next::Cell(cell, alignment, *head)),
+            };
+            let event = match next.unwrap_or(Next::Done) {
+                Next::Block(block, tight) => self.enter_block(block, tight),
+                Next::Item(item, tight) => {
+                    let span = item.span;
+                    let mut children = item.children.into_iter();
+                    let paragraph = match children.as_slice().first() {
+                        Some(Block::Paragraph(_)) if !tight && item.checked.is_some() => {
+                            children.next()
+                        }
+                        _ => None,
+                    };
+                    let start = self.push(Nodes::Blocks(children, tight), Tag::Item, span);
+                    let mut events = Vec::new();
+                    if let Some(Block::Paragraph(p)) = paragraph {
+                        events.push((Event::Start(Tag::Paragraph), p.span));
+                        self.stack.push(Frame {
+                            nodes: Nodes::Inlines(p.children.into_iter()),
+                            end: Some((Tag::Paragraph, p.span)),
+                        });
+                    }
+                    if let Some(checked) = item.checked {
+                        events.push((Event::TaskListMarker(checked), span));
+                        self.stack.push(Frame {
+                            nodes: Nodes::Events(events.into_iter()),
+                            end: None,
+                        });
+                    }
+                    start
+                }
+                Next::Inline(inline) => self.enter_inline(inline),
+                Next::Event(event, span) => Some((event, span)),
+                Next::Section(tag, rows, span, alignments) => {
+                    let head = tag == Tag::TableHead;
+                    self.push(Nodes::Rows(rows.into_iter(), alignments, head), tag, span)
+                }
+                Next::Row(row, alignments, head) => {
+                    let cells: Vec<_> = row.cells.into_iter().zip(alignments).collect();
+                    self.push(
+                        Nodes::Cells(cells.into_iter(), head),
+                        Tag::TableRow,
+                        row.span,
+                    )
+                }
+                Next::Cell(cell, alignment, head) => {
+                    let tag = if head {
+                        Tag::HeaderCell(alignment)
+                    } else {
+                        Tag::TableCell(alignment)
+                    };
+                    self.push(Nodes::Inlines(cell.children.into_iter()), tag, cell.span)
+                }
+                Next::Done => {
+                    let frame = self.stack.pop()?;
+                    frame.end.map(|(tag, span)| (Event::End(tag), span))
+                }
+            };
+            if event.is_some() {
+                return event;
+            }
+        }
+    }
+
+    /// Pushes the frame of a node with children and returns its `Start`.
+    fn push(&mut self, nodes: Nodes, tag: Tag, span: Span) -> Option<(Event, Span)> {
+        self.stack.push(Frame {
+            nodes,
+            end: Some((tag.clone(), span)),
+        });
+        Some((Event::Start(tag), span))
+    }
+
+    /// Pushes the frame of a node whose contents are a single event.
+    fn push_leaf(&mut self, tag: Tag, content: Option<Event>, span: Span) -> Option<(Event, Span)> {
+        let events: Vec<(Event, Span)> = content.into_iter().map(|e| (e, span)).collect();
+        self.push(Nodes::Events(events.into_iter()), tag, span)
+    }
+
+    /// Returns the first event of `block`, if it has any, and pushes what is
+    /// left of it.
+    fn enter_block(&mut self, block: Block, tight: bool) -> Option<(Event, Span)> {
+        match block {
+            Block::Heading(b) => {
+                let nodes = Nodes::Inlines(b.children.into_iter());
+                self.push(nodes, Tag::Heading(b.level), b.span)
+            }
+            Block::Paragraph(b) if tight => {
+                self.stack.push(Frame {
+                    nodes: Nodes::Inlines(b.children.into_iter()),
+                    end: None,
+                });
+                None
+            }
+            Block::Paragraph(b) => self.push(
+                Nodes::Inlines(b.children.into_iter()),
+                Tag::Paragraph,
+                b.span,
+            ),
+            Block::CodeBlock(b) => {
+                let text = if b.text.is_empty() {
+                    None
+                } else {
+                    Some(Event::Text(b.text))
+                };
+                self.push_leaf(Tag::CodeBlock(b.info), text, b.span)
+            }
+            Block::BlockQuote(b) => {
+                let nodes = Nodes::Blocks(b.children.into_iter(), false);
+                self.push(nodes, Tag::BlockQuote, b.span)
+            }
+            Block::List(b) => {
+                let nodes = Nodes::Items(b.items.into_iter(), b.tight);
+                self.push(nodes, Tag::List(b.start), b.span)
+            }
+            Block::LinkDefinition(b) => Some((Event::LinkDefinition(b.label, b.url), b.span)),
+            Block::ThematicBreak(span) => Some((Event::Rule, span)),
+            Block::Table(b) => {
+                let head_span = b.head.span;
+                let mut sections = vec![(Tag::TableHead, vec![b.head], head_span)];
+                if let (Some(first), Some(last)) = (b.rows.first(), b.rows.last()) {
+                    let span = Span::new(first.span.start, last.span.end);
+                    sections.push((Tag::TableBody, b.rows, span));
+                }
+                let nodes = Nodes::Sections(sections.into_iter(), b.alignments.clone());
+                self.push(nodes, Tag::Table(b.alignments), b.span)
+            }
+        }
+    }
+
+    /// Returns the first event of `inline` and pushes what is left of it.
+    fn enter_inline(&mut self, inline: Inline) -> Option<(Event, Span)> {
+        let span = inline.span();
+        match inline {
+            Inline::Text(i) => Some((Event::Text(i.text), span)),
+            Inline::Emphasis(i) => {
+                self.push(Nodes::Inlines(i.children.into_iter()), Tag::Emphasis, span)
+            }
+            Inline::Strong(i) => {
+                self.push(Nodes::Inlines(i.children.into_iter()), Tag::Strong, span)
+            }
+            Inline::Strikethrough(i) => self.push(
+                Nodes::Inlines(i.children.into_iter()),
+                Tag::Strikethrough,
+                span,
+            ),
+            Inline::Code(i) => Some((Event::Code(i.code), span)),
+            Inline::Link(i) => {
+                self.push_leaf(Tag::Link(i.destination), Some(Event::Text(i.text)), span)
+            }
+            Inline::Image(i) => {
+                self.push_leaf(Tag::Image(i.destination), Some(Event::Text(i.alt)), span)
+            }
+            Inline::HardBreak(_) => Some((Event::HardBreak, span)),
+            Inline::SoftBreak(_) => Some((Event::SoftBreak, span)),
+        }
+    }
+}
+
+impl Iterator for Events {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Event> {
+        self.next_spanned().map(|(event, _)| event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(text: &str) -> Vec<Event> {
+        Events::new(Document::parse(text)).collect()
+    }
+
+    #[test]
+    fn test_events_of_paragraph() {
+        assert_eq!(
+            events("a *b* `c`\nd"),
+            vec![
+                Event::Start(Tag::Paragraph),
+                Event::Text("a ".to_string()),
+                Event::Start(Tag::Emphasis),
+                Event::Text("b".to_string()),
+                Event::End(Tag::Emphasis),
+                Event::Text(" ".to_string()),
+                Event::Code("c".to_string()),
+                Event::SoftBreak,
+                Event::Text("d".to_string()),
+                Event::End(Tag::Paragraph),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tight_list_has_no_paragraphs() {
+        assert_eq!(
+            events("- a\n- b"),
+            vec![
+                Event::Start(Tag::List(None)),
+                Event::Start(Tag::Item),
+                Event::Text("a".to_string()),
+                Event::End(Tag::Item),
+                Event::Start(Tag::Item),
+                Event::Text("b".to_string()),
+                Event::End(Tag::Item),
+                Event::End(Tag::List(None)),
+            ]
+        );
+        assert!(events("- a\n\n- b").contains(&Event::Start(Tag::Paragraph)));
+    }
+
+    #[test]
+    fn test_reference_links_are_resolved() {
+        let all = events("[a][x] [b][y]\n\n[x]:/url");
+        assert_eq!(
+            all[1..6],
+            [
+                Event::Start(Tag::Link("/url".to_string())),
+                Event::Text("a".to_string()),
+                Event::End(Tag::Link("/url".to_string())),
+                Event::Text(" ".to_string()),
+                Event::Text("[b][y]".to_string()),
+            ]
+        );
+        assert_eq!(
+            all.last(),
+            Some(&Event::LinkDefinition("x".to_string(), "/url".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_spanned_events() {
+        let text = "# a\n\n***";
+        let spanned: Vec<(Event, Span)> =
+            Events::new(Document::parse(text)).into_spanned().collect();
+        let (start, span) = &spanned[0];
+        assert_eq!(start, &Event::Start(Tag::Heading(1)));
+        assert_eq!((span.start.offset, span.end.offset), (0, 3));
+        let (rule, span) = spanned.last().unwrap();
+        assert_eq!(rule, &Event::Rule);
+        assert_eq!(&text[span.start.offset..span.end.offset], "***");
+    }
+}
@@ -0,0 +1,654 @@
+//! Rendering as HTML, from events with `push_html` or from the node tree with
+//! an `HtmlRenderer`.
+//!
+//! The functions named after nodes, such as `heading` and `image`, are the
+//! default methods of `Renderer`: each writes its node as HTML and renders the
+//! node's children through the renderer it is given.
+use super::event::{Event, Tag};
+use super::node::{
+    Block, BlockQuote, Code, CodeBlock, Emphasis, Heading, Image, Link, LinkDefinition, List,
+    ListItem, Paragraph, Strikethrough, Strong, Table, TableCell, TableRow, Text,
+};
+use super::options::{is_safe_url, ParserOptions, SoftBreakStyle};
+use super::render::{render_blocks, render_inlines, Output, Renderer};
+use super::tokenizer::{Alignment, Span};
+
+use std::fmt::{self, Write};
+
+/// Escapes the characters that are special in HTML text and attribute values:
+/// `&`, `<`, `>` and `"`.
+pub fn escape_html(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    // Writing to a `String` never fails.
+    let _ = write_escaped(&mut result, text);
+    result
+}
+
+/// Writes `text` to `output` with the characters that `escape_html` escapes
+/// replaced, without building the escaped string first.
+pub fn write_escaped<W: Write + ?Sized>(output: &mut W, text: &str) -> fmt::Result {
+    let mut rest = text;
+    while let Some(i) = rest.find(['&', '<', '>', '"']) {
+        output.write_str(&rest[..i])?;
+        output.write_str(match rest.as_bytes()[i] {
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            _ => "&quot;",
+        })?;
+        rest = &rest[i + 1..];
+    }
+    output.write_str(rest)
+}
+
+/// Appends the HTML of `events` to `output`.
+pub fn push_html<I: Iterator<Item = Event>>(output: &mut String, events: I) {
+    let events = events.map(|e| (e, Span::default()));
+    push_html_spanned(output, events, &ParserOptions::default());
+}
+
+/// Appends the HTML of `events` to `output`, written the way `options` says.
+/// The spans are only used for `options.source_positions`.
+pub fn push_html_spanned<I: Iterator<Item = (Event, Span)>>(
+    output: &mut String,
+    events: I,
+    options: &ParserOptions,
+) {
+    let last = output.chars().last();
+    // Writing to a `String` never fails.
+    let _ = write_html_spanned(&mut Output::after(output, last), events, options);
+}
+
+/// Writes the HTML of `events` to `output` as they come, and stops at the
+/// first error of `output`.
+pub fn write_html<W, I>(output: &mut W, events: I) -> fmt::Result
+where
+    W: Write,
+    I: Iterator<Item = Event>,
+{
+    let events = events.map(|e| (e, Span::default()));
+    write_html_spanned(&mut Output::new(output), events, &ParserOptions::default())
+}
+
+fn write_html_spanned<I: Iterator<Item = (Event, Span)>>(
+    output: &mut Output,
+    events: I,
+    options: &ParserOptions,
+) -> fmt::Result {
+    for (event, span) in events {
+        write_event(output, &event, span, options)?;
+    }
+    Ok(())
+}
+
+/// Renders the node tree as HTML with the default methods of `Renderer`.
+#[derive(Debug, Default)]
+pub struct HtmlRenderer {
+    options: ParserOptions,
+}
+
+impl HtmlRenderer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_options(options: ParserOptions) -> Self {
+        HtmlRenderer { options }
+    }
+
+    /// Gives every block element a `data-sourcepos` attribute with the lines
+    /// and columns of its source text.
+    pub fn set_source_positions(&mut self, enabled: bool) {
+        self.options.source_positions = enabled;
+    }
+}
+
+impl Renderer for HtmlRenderer {
+    fn options(&self) -> ParserOptions {
+        self.options
+    }
+}
+
+pub fn heading<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    heading: &Heading,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = Tag::Heading(heading.level);
+    write_start(output, &tag, heading.span, &options)?;
+    render_inlines(renderer, output, &heading.children)?;
+    write_end(output, &tag, &options)
+}
+
+/// Writes a paragraph, without `<p>` if it is in a tight list.
+pub fn paragraph<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    paragraph: &Paragraph,
+    tight: bool,
+) -> fmt::Result {
+    if tight {
+        return render_inlines(renderer, output, &paragraph.children);
+    }
+    let options = renderer.options();
+    write_start(output, &Tag::Paragraph, paragraph.span, &options)?;
+    render_inlines(renderer, output, &paragraph.children)?;
+    write_end(output, &Tag::Paragraph, &options)
+}
+
+pub fn code_block<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    code_block: &CodeBlock,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = Tag::CodeBlock(code_block.info.clone());
+    write_start(output, &tag, code_block.span, &options)?;
+    write_escaped(output, &code_block.text)?;
+    write_end(output, &tag, &options)
+}
+
+pub fn block_quote<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    block_quote: &BlockQuote,
+) -> fmt::Result {
+    let options = renderer.options();
+    write_start(output, &Tag::BlockQuote, block_quote.span, &options)?;
+    render_blocks(renderer, output, &block_quote.children, false)?;
+    write_end(output, &Tag::BlockQuote, &options)
+}
+
+pub fn list<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    list: &List,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = Tag::List(list.start);
+    write_start(output, &tag, list.span, &options)?;
+    for item in &list.items {
+        renderer.list_item(output, item, list.tight)?;
+    }
+    write_end(output, &tag, &options)
+}
+
+pub fn list_item<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    item: &ListItem,
+    tight: bool,
+) -> fmt::Result {
+    let options = renderer.options();
+    write_start(output, &Tag::Item, item.span, &options)?;
+    let mut children = &item.children[..];
+    if let Some(checked) = item.checked {
+        let marker = Event::TaskListMarker(checked);
+        match children.first() {
+            // As in GFM, the checkbox of a loose item starts its first
+            // paragraph, which is written here rather than by the renderer.
+            Some(Block::Paragraph(p)) if !tight => {
+                write_start(output, &Tag::Paragraph, p.span, &options)?;
+                write_event(output, &marker, item.span, &options)?;
+                render_inlines(renderer, output, &p.children)?;
+                write_end(output, &Tag::Paragraph, &options)?;
+                children = &children[1..];
+            }
+            _ => write_event(output, &marker, item.span, &options)?,
+        }
+    }
+    render_blocks(renderer, output, children, tight)?;
+    write_end(output, &Tag::Item, &options)
+}
+
+pub fn link_definition<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    definition: &LinkDefinition,
+) -> fmt::Result {
+    let event = Event::LinkDefinition(definition.label.clone(), definition.url.clone());
+    write_event(output, &event, definition.span, &renderer.options())
+}
+
+pub fn thematic_break<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    span: Span,
+) -> fmt::Result {
+    write_event(output, &Event::Rule, span, &renderer.options())
+}
+
+/// Writes a table, with a `<tbody>` only if it has rows besides the header.
+pub fn table<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    table: &Table,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = Tag::Table(table.alignments.clone());
+    write_start(output, &tag, table.span, &options)?;
+    write_start(output, &Tag::TableHead, table.head.span, &options)?;
+    table_row(renderer, output, &table.head, &table.alignments, true)?;
+    write_end(output, &Tag::TableHead, &options)?;
+    if let (Some(first), Some(last)) = (table.rows.first(), table.rows.last()) {
+        let span = Span::new(first.span.start, last.span.end);
+        write_start(output, &Tag::TableBody, span, &options)?;
+        for row in &table.rows {
+            table_row(renderer, output, row, &table.alignments, false)?;
+        }
+        write_end(output, &Tag::TableBody, &options)?;
+    }
+    write_end(output, &tag, &options)
+}
+
+fn table_row<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    row: &TableRow,
+    alignments: &[Alignment],
+    head: bool,
+) -> fmt::Result {
+    let options = renderer.options();
+    write_start(output, &Tag::TableRow, row.span, &options)?;
+    for (cell, alignment) in row.cells.iter().zip(alignments) {
+        renderer.table_cell(output, cell, *alignment, head)?;
+    }
+    write_end(output, &Tag::TableRow, &options)
+}
+
+/// Writes a `<th>` cell of the header row, or a `<td>` cell of a body row.
+pub fn table_cell<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    cell: &TableCell,
+    alignment: Alignment,
+    head: bool,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = if head {
+        Tag::HeaderCell(alignment)
+    } else {
+        Tag::TableCell(alignment)
+    };
+    write_start(output, &tag, cell.span, &options)?;
+    render_inlines(renderer, output, &cell.children)?;
+    write_end(output, &tag, &options)
+}
+
+pub fn text<R: Renderer + ?Sized>(
+    _renderer: &mut R,
+    output: &mut Output,
+    text: &Text,
+) -> fmt::Result {
+    write_escaped(output, &text.text)
+}
+
+pub fn emphasis<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    emphasis: &Emphasis,
+) -> fmt::Result {
+    let options = renderer.options();
+    write_start(output, &Tag::Emphasis, emphasis.span, &options)?;
+    render_inlines(renderer, output, &emphasis.children)?;
+    write_end(output, &Tag::Emphasis, &options)
+}
+
+pub fn strong<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    strong: &Strong,
+) -> fmt::Result {
+    let options = renderer.options();
+    write_start(output, &Tag::Strong, strong.span, &options)?;
+    render_inlines(renderer, output, &strong.children)?;
+    write_end(output, &Tag::Strong, &options)
+}
+
+pub fn strikethrough<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    strikethrough: &Strikethrough,
+) -> fmt::Result {
+    let options = renderer.options();
+    write_start(output, &Tag::Strikethrough, strikethrough.span, &options)?;
+    render_inlines(renderer, output, &strikethrough.children)?;
+    write_end(output, &Tag::Strikethrough, &options)
+}
+
+pub fn code<R: Renderer + ?Sized>(
+    _renderer: &mut R,
+    output: &mut Output,
+    code: &Code,
+) -> fmt::Result {
+    write_code(output, &code.code)
+}
+
+/// Writes a link to its destination, which is taken as a URL even if the link
+/// is a reference.
+pub fn link<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    link: &Link,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = Tag::Link(link.destination.clone());
+    write_start(output, &tag, link.span, &options)?;
+    write_escaped(output, &link.text)?;
+    write_end(output, &tag, &options)
+}
+
+/// Writes an image of its destination, which is taken as a URL even if the
+/// image is a reference.
+pub fn image<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    image: &Image,
+) -> fmt::Result {
+    let options = renderer.options();
+    let tag = Tag::Image(image.destination.clone());
+    write_start(output, &tag, image.span, &options)?;
+    write_escaped(output, &image.alt)?;
+    write_end(output, &tag, &options)
+}
+
+pub fn hard_break<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    span: Span,
+) -> fmt::Result {
+    write_event(output, &Event::HardBreak, span, &renderer.options())
+}
+
+pub fn soft_break<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    span: Span,
+) -> fmt::Result {
+    write_event(output, &Event::SoftBreak, span, &renderer.options())
+}
+
+/// Starts a new line unless `output` is empty or already at the start of one,
+/// so that every block element starts on its own line.
+fn ensure_newline(output: &mut Output) -> fmt::Result {
+    if output.at_line_start() {
+        Ok(())
+    } else {
+        output.write_char('\n')
+    }
+}
+
+/// Starts a block element: `<name` on a line of its own, followed by the
+/// `data-sourcepos` attribute for `span` if `options` asks for it. Its end
+/// column is inclusive, so an empty span, such as that of a table cell the
+/// row leaves out, gets no attribute.
+fn open_block(output: &mut Output, name: &str, span: Span, options: &ParserOptions) -> fmt::Result {
+    ensure_newline(output)?;
+    output.write_char('<')?;
+    output.write_str(name)?;
+    if !options.source_positions || span.is_empty() {
+        return Ok(());
+    }
+    write!(
+        output,
+        " data-sourcepos=\"{}:{}-{}:{}\"",
+        span.start.line,
+        span.start.column,
+        span.end.line,
+        span.end.column.saturating_sub(1)
+    )
+}
+
+/// The end of an empty element such as `<br>`.
+fn void_end(options: &ParserOptions) -> &'static str {
+    if options.xhtml {
+        " />"
+    } else {
+        ">"
+    }
+}
+
+/// Writes `url` as an attribute value, or nothing if safe mode drops it.
+fn write_url(output: &mut Output, url: &str, options: &ParserOptions) -> fmt::Result {
+    if options.safe && !is_safe_url(url) {
+        return Ok(());
+    }
+    write_escaped(output, url)
+}
+
+/// Starts a table cell, with an `align` attribute unless its column has no
+/// alignment.
+fn open_cell(
+    output: &mut Output,
+    name: &str,
+    alignment: Alignment,
+    span: Span,
+    options: &ParserOptions,
+) -> fmt::Result {
+    open_block(output, name, span, options)?;
+    match alignment {
+        Alignment::None => output.write_char('>'),
+        Alignment::Left => output.write_str(" align=\"left\">"),
+        Alignment::Center => output.write_str(" align=\"center\">"),
+        Alignment::Right => output.write_str(" align=\"right\">"),
+    }
+}
+
+fn write_code(output: &mut Output, code: &str) -> fmt::Result {
+    output.write_str("<code>")?;
+    write_escaped(output, code)?;
+    output.write_str("</code>")
+}
+
+fn write_event(
+    output: &mut Output,
+    event: &Event,
+    span: Span,
+    options: &ParserOptions,
+) -> fmt::Result {
+    match event {
+        Event::Start(tag) => write_start(output, tag, span, options),
+        Event::End(tag) => write_end(output, tag, options),
+        Event::Text(text) => write_escaped(output, text),
+        Event::Code(code) => write_code(output, code),
+        Event::SoftBreak => match options.soft_break {
+            SoftBreakStyle::Newline => output.write_char('\n'),
+            SoftBreakStyle::Space => output.write_char(' '),
+            SoftBreakStyle::Hard => write_event(output, &Event::HardBreak, span, options),
+        },
+        Event::HardBreak => {
+            output.write_str("<br")?;
+            output.write_str(void_end(options))
+        }
+        Event::Rule => {
+            open_block(output, "hr", span, options)?;
+            output.write_str(void_end(options))?;
+            output.write_char('\n')
+        }
+        Event::LinkDefinition(_, _) => {
+            ensure_newline(output)?;
+            output.write_char('\n')
+        }
+        Event::TaskListMarker(checked) => {
+            output.write_str("<input type=\"checkbox\"")?;
+            if *checked {
+                output.write_str(" checked=\"\"")?;
+            }
+            output.write_str(" disabled=\"\"")?;
+            output.write_str(void_end(options))?;
+            output.write_char(' ')
+        }
+    }
+}
+
+fn write_start(output: &mut Output, tag: &Tag, span: Span, options: &ParserOptions) -> fmt::Result {
+    match tag {
+        Tag::Paragraph => {
+            open_block(output, "p", span, options)?;
+            output.write_str(">\n")
+        }
+        Tag::Heading(level) => {
+            open_block(output, &format!("h{}", level), span, options)?;
+            output.write_char('>')
+        }
+        Tag::BlockQuote => {
+            open_block(output, "blockquote", span, options)?;
+            output.write_str(">\n")
+        }
+        Tag::CodeBlock(info) => {
+            open_block(output, "pre", span, options)?;
+            match info
+                .as_ref()
+                .and_then(|info| info.split_whitespace().next())
+            {
+                Some(language) => {
+                    output.write_str("><code class=\"language-")?;
+                    write_escaped(output, language)?;
+                    output.write_str("\">\n")
+                }
+                None => output.write_str("><code>\n"),
+            }
+        }
+        Tag::List(None) => {
+            open_block(output, "ul", span, options)?;
+            output.write_str(">\n")
+        }
+        Tag::List(Some(1)) => {
+            open_block(output, "ol", span, options)?;
+            output.write_str(">\n")
+        }
+        Tag::List(Some(start)) => {
+            open_block(output, "ol", span, options)?;
+            writeln!(output, " start=\"{}\">", start)
+        }
+        Tag::Item => {
+            open_block(output, "li", span, options)?;
+            output.write_char('>')
+        }
+        Tag::Emphasis => output.write_str("<em>"),
+        Tag::Strong => output.write_str("<strong>"),
+        Tag::Strikethrough => output.write_str("<del>"),
+        Tag::Link(url) => {
+            output.write_str("<a href=\"")?;
+            write_url(output, url, options)?;
+            output.write_str("\">")
+        }
+        Tag::Image(url) => {
+            output.write_str("<img src=\"")?;
+            write_url(output, url, options)?;
+            output.write_str("\" alt=\"")
+        }
+        Tag::Table(_) => {
+            open_block(output, "table", span, options)?;
+            output.write_str(">\n")
+        }
+        Tag::TableHead => output.write_str("<thead>\n"),
+        Tag::TableBody => output.write_str("<tbody>\n"),
+        Tag::TableRow => {
+            open_block(output, "tr", span, options)?;
+            output.write_str(">\n")
+        }
+        Tag::HeaderCell(alignment) => open_cell(output, "th", *alignment, span, options),
+        Tag::TableCell(alignment) => open_cell(output, "td", *alignment, span, options),
+    }
+}
+
+fn write_end(output: &mut Output, tag: &Tag, options: &ParserOptions) -> fmt::Result {
+    match tag {
+        Tag::Paragraph => output.write_str("\n</p>\n"),
+        Tag::Heading(level) => writeln!(output, "</h{}>", level),
+        Tag::BlockQuote => output.write_str("</blockquote>\n"),
+        Tag::CodeBlock(_) => output.write_str("\n</code></pre>\n"),
+        Tag::List(None) => output.write_str("</ul>\n"),
+        Tag::List(Some(_)) => output.write_str("</ol>\n"),
+        Tag::Item => output.write_str("</li>\n"),
+        Tag::Emphasis => output.write_str("</em>"),
+        Tag::Strong => output.write_str("</strong>"),
+        Tag::Strikethrough => output.write_str("</del>"),
+        Tag::Link(_) => output.write_str("</a>"),
+        Tag::Image(_) => {
+            output.write_char('"')?;
+            output.write_str(void_end(options))
+        }
+        Tag::Table(_) => output.write_str("</table>\n"),
+        Tag::TableHead => output.write_str("</thead>\n"),
+        Tag::TableBody => output.write_str("</tbody>\n"),
+        Tag::TableRow => output.write_str("</tr>\n"),
+        Tag::HeaderCell(_) => output.write_str("</th>\n"),
+        Tag::TableCell(_) => output.write_str("</td>\n"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::Events;
+    use crate::node::Document;
+
+    #[test]
+    fn test_push_html_of_events() {
+        let mut output = String::new();
+        let events = vec![
+            Event::Start(Tag::Heading(2)),
+            Event::Text("a < b".to_string()),
+            Event::End(Tag::Heading(2)),
+            Event::Rule,
+        ];
+        push_html(&mut output, events.into_iter());
+        assert_eq!(output, "<h2>a &lt; b</h2>\n<hr>\n");
+    }
+
+    #[test]
+    fn test_push_html_of_changed_events() {
+        let events = Events::new(Document::parse("*a* **b**")).map(|event| match event {
+            Event::Start(Tag::Emphasis) => Event::Start(Tag::Strong),
+            Event::End(Tag::Emphasis) => Event::End(Tag::Strong),
+            event => event,
+        });
+        let mut output = String::new();
+        push_html(&mut output, events);
+        assert_eq!(output, "<p>\n<strong>a</strong> <strong>b</strong>\n</p>\n");
+    }
+
+    #[test]
+    fn test_write_escaped() {
+        let mut output = String::new();
+        write_escaped(&mut output, "<a href=\"x\">&</a> é").unwrap();
+        assert_eq!(output, "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt; é");
+    }
+
+    #[test]
+    fn test_safe_mode_reads_urls_like_browsers() {
+        let options = ParserOptions {
+            safe: true,
+            ..ParserOptions::default()
+        };
+        for url in ["java\tscript:x", "java\nscript:x", "\u{1}javascript:x"] {
+            let mut output = String::new();
+            for tag in [Tag::Link(url.to_string()), Tag::Image(url.to_string())] {
+                let mut output = Output::new(&mut output);
+                write_start(&mut output, &tag, Span::default(), &options).unwrap();
+            }
+            assert_eq!(output, "<a href=\"\"><img src=\"\" alt=\"", "{:?}", url);
+        }
+        // A newline can't be written inside a link destination.
+        for url in ["java\tscript:x", "\u{1}javascript:x"] {
+            let document = Document::parse(&format!("[a]({}) ![b]({})", url, url));
+            assert_eq!(
+                HtmlRenderer::with_options(options).render(&document),
+                "<p>\n<a href=\"\">a</a> <img src=\"\" alt=\"b\">\n</p>\n",
+                "{:?}",
+                url
+            );
+        }
+    }
+
+    #[test]
+    fn test_write_html_of_events() {
+        let mut output = String::new();
+        let events = Events::new(Document::parse("- a\n- b"));
+        write_html(&mut output, events).unwrap();
+        assert_eq!(output, "<ul>\n<li>a</li>\n<li>b</li>\n</ul>\n");
+    }
+}
@@ -1,6 +1,19 @@
+#![doc = include_str!("../README.md")]
+pub mod error;
+pub use error::Error;
 pub mod tokenizer;
 pub use tokenizer::inline_token::InlineToken;
 pub use tokenizer::line_token::LineToken;
 pub use tokenizer::Tokenizer;
+pub mod event;
+pub use event::{Event, Events, Tag};
+pub mod html;
+pub use html::HtmlRenderer;
+pub mod node;
+pub use node::Document;
+pub mod render;
+pub use render::{Output, Renderer};
+pub mod options;
+pub use options::{Extensions, ParserOptions, SoftBreakStyle};
 pub mod parser;
-pub use parser::Parser;
+pub use parser::{Diagnostic, Parser};
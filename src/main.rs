@@ -0,0 +1,105 @@
+//! `mustdown [OPTIONS] [FILE]...`: renders Markdown files, or the standard
+//! input, as HTML on the standard output.
+extern crate mustdown;
+
+use mustdown::{Extensions, Parser, ParserOptions, SoftBreakStyle};
+use std::env;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+const USAGE: &str = "Usage: mustdown [OPTIONS] [FILE]...
+
+Renders the Markdown FILEs, or the standard input if there are none, as HTML.
+
+Options:
+  --gfm                 turn on every GitHub Flavored Markdown extension
+  --safe                drop link and image URLs that could run code
+  --xhtml               write empty elements as <br /> and the like
+  --sourcepos           add data-sourcepos attributes to block elements
+  --soft-break=STYLE    write soft line breaks as newline, space or hard
+  -h, --help            show this help";
+
+/// Reads the options and the files from the command line arguments.
+fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<(ParserOptions, Vec<String>), String> {
+    let mut options = ParserOptions::default();
+    let mut files = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--gfm" => options.extensions = Extensions::gfm(),
+            "--safe" => options.safe = true,
+            "--xhtml" => options.xhtml = true,
+            "--sourcepos" => options.source_positions = true,
+            "--soft-break=newline" => options.soft_break = SoftBreakStyle::Newline,
+            "--soft-break=space" => options.soft_break = SoftBreakStyle::Space,
+            "--soft-break=hard" => options.soft_break = SoftBreakStyle::Hard,
+            a if a.starts_with('-') && a != "-" => {
+                return Err(format!("unknown option {}\n\n{}", a, USAGE))
+            }
+            _ => files.push(arg),
+        }
+    }
+    Ok((options, files))
+}
+
+fn read_stdin() -> io::Result<String> {
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Reads the files, or the standard input for `-` or no files at all, and
+/// joins them into one document.
+fn read_input(files: &[String]) -> io::Result<String> {
+    if files.is_empty() {
+        return read_stdin();
+    }
+    let texts = files
+        .iter()
+        .map(|file| match file.as_str() {
+            "-" => read_stdin(),
+            _ => fs::read_to_string(file),
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    Ok(texts.join("\n"))
+}
+
+fn main() {
+    if env::args().any(|a| a == "-h" || a == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+    let (options, files) = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(message) => {
+            eprintln!("{}", message);
+            process::exit(2);
+        }
+    };
+    let result = read_input(&files)
+        .and_then(|text| Parser::with_options(options).render_to(&text, io::stdout().lock()));
+    if let Err(e) = result {
+        eprintln!("mustdown: {}", e);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Result<(ParserOptions, Vec<String>), String> {
+        parse_args(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let (options, files) = args("--gfm --safe a.md --soft-break=hard - b.md").unwrap();
+        assert_eq!(options.extensions, Extensions::gfm());
+        assert!(options.safe && !options.xhtml && !options.source_positions);
+        assert_eq!(options.soft_break, SoftBreakStyle::Hard);
+        assert_eq!(files, vec!["a.md", "-", "b.md"]);
+        assert_eq!(args("").unwrap(), (ParserOptions::default(), Vec::new()));
+        assert!(args("--soft-break=tab").is_err());
+    }
+}
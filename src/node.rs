@@ -0,0 +1,747 @@
+//! The document as a tree of nodes: a `Document` holds `Block`s, and blocks
+//! hold either more blocks or `Inline`s.
+//!
+//! The tokenizer's `LineToken` and `InlineToken` mirror how the text was read,
+//! so a list is an `OrderedListBlock` of `LineToken::OrderedList` items and
+//! emphasis is a `SpecialToken` with a symbol. The tree has one type per kind
+//! of node instead, and every node keeps the span of its source text. Walk it
+//! with a `Visitor` or `VisitorMut`.
+use super::options::{is_safe_url, Extensions};
+use super::tokenizer::inline_token::InlineToken;
+use super::tokenizer::line_token::LineToken;
+use super::tokenizer::{self, Alignment, Span, Tokenizer};
+use super::Error;
+
+use std::collections::HashMap;
+
+pub mod visit;
+
+pub use visit::{Visitor, VisitorMut};
+
+/// The root of the tree.
+#[derive(Debug, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    pub children: Vec<Block>,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Block {
+    Heading(Heading),
+    Paragraph(Paragraph),
+    CodeBlock(CodeBlock),
+    BlockQuote(BlockQuote),
+    List(List),
+    LinkDefinition(LinkDefinition),
+    ThematicBreak(Span),
+    Table(Table),
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Inline {
+    Text(Text),
+    Emphasis(Emphasis),
+    Strong(Strong),
+    Strikethrough(Strikethrough),
+    Code(Code),
+    Link(Link),
+    Image(Image),
+    HardBreak(Span),
+    SoftBreak(Span),
+}
+
+/// An ATX (`# title`) or setext (underlined) heading.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Heading {
+    pub level: usize,
+    pub children: Vec<Inline>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Paragraph {
+    pub children: Vec<Inline>,
+    pub span: Span,
+}
+
+/// A fenced or indented code block.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CodeBlock {
+    /// The info string after the opening fence, if any.
+    pub info: Option<String>,
+    pub text: String,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BlockQuote {
+    pub children: Vec<Block>,
+    pub span: Span,
+}
+
+/// A run of list items with the same kind of marker.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct List {
+    /// The number of the first item of an ordered list, `None` for a bullet
+    /// list.
+    pub start: Option<usize>,
+    /// The bullet, or the `.` or `)` after the numbers of an ordered list.
+    pub symbol: char,
+    /// Whether the paragraphs of the items are shown without `<p>`.
+    pub tight: bool,
+    pub items: Vec<ListItem>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ListItem {
+    /// Whether the item is a checked task, `None` if it isn't a task.
+    pub checked: Option<bool>,
+    pub children: Vec<Block>,
+    pub span: Span,
+}
+
+/// A `[label]: url` line, which reference links and images look up.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkDefinition {
+    pub label: String,
+    pub url: String,
+    pub span: Span,
+}
+
+/// A GFM table. Every row has one cell per column.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub head: TableRow,
+    pub rows: Vec<TableRow>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableRow {
+    pub cells: Vec<TableCell>,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TableCell {
+    pub children: Vec<Inline>,
+    pub span: Span,
+}
+
+/// The task list items of a document, counted by `Document::task_count`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TaskCount {
+    /// The checked items.
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Text {
+    pub text: String,
+    pub span: Span,
+}
+
+/// Emphasis written with one `*` or `_`.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Emphasis {
+    pub symbol: char,
+    pub children: Vec<Inline>,
+    pub span: Span,
+}
+
+/// Strong emphasis written with `**` or `__`.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Strong {
+    pub symbol: char,
+    pub children: Vec<Inline>,
+    pub span: Span,
+}
+
+/// Text struck through with `~` or `~~`.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Strikethrough {
+    pub children: Vec<Inline>,
+    pub span: Span,
+}
+
+/// A code span.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Code {
+    pub code: String,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Link {
+    pub text: String,
+    /// The URL, or the label of a `LinkDefinition` if `reference` is set.
+    pub destination: String,
+    pub reference: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Image {
+    pub alt: String,
+    /// The URL, or the label of a `LinkDefinition` if `reference` is set.
+    pub destination: String,
+    pub reference: bool,
+    pub span: Span,
+}
+
+impl Document {
+    /// Builds the tree of `text`. Never panics; see `try_parse` for the errors
+    /// behind that.
+    pub fn parse(text: &str) -> Document {
+        Document::try_parse(text).unwrap_or_default()
+    }
+
+    pub fn try_parse(text: &str) -> Result<Document, Error> {
+        Document::try_parse_with(text, Extensions::default())
+    }
+
+    /// Builds the tree of `text` with the syntax of `extensions` on top of
+    /// CommonMark.
+    pub fn try_parse_with(text: &str, extensions: Extensions) -> Result<Document, Error> {
+        Document::from_tokens(Tokenizer::try_tokenize_with(text, extensions)?)
+    }
+
+    /// Builds the tree from the tokens of a document. Fails on tokens the
+    /// tokenizer doesn't build, such as a list block holding something other
+    /// than list items.
+    pub fn from_tokens(tokens: Vec<LineToken>) -> Result<Document, Error> {
+        Ok(Document {
+            children: Block::from_tokens(tokens)?,
+        })
+    }
+
+    /// The URLs of the document's link definitions, by label.
+    pub fn definitions(&self) -> HashMap<String, String> {
+        let mut collector = DefinitionCollector::default();
+        collector.visit_document(self);
+        collector.definitions
+    }
+
+    /// Counts the task list items of the document, and the checked ones among
+    /// them, in nested lists too.
+    pub fn task_count(&self) -> TaskCount {
+        let mut counter = TaskCounter::default();
+        counter.visit_document(self);
+        counter.count
+    }
+
+    /// Turns every reference link and image into a plain one with the URL
+    /// that `definitions` has for its label. Those whose label has no
+    /// definition become the `Text` they were written as, `[text][label]` or
+    /// `![alt][label]`.
+    pub fn resolve_references(&mut self, definitions: &HashMap<String, String>) {
+        ReferenceResolver { definitions }.visit_document_mut(self);
+    }
+
+    /// Empties the URL of every link, image and link definition that safe
+    /// mode wouldn't write, so that any renderer leaves them out.
+    pub fn drop_unsafe_urls(&mut self) {
+        UnsafeUrlDropper.visit_document_mut(self);
+    }
+}
+
+#[derive(Default)]
+struct DefinitionCollector {
+    definitions: HashMap<String, String>,
+}
+
+impl Visitor for DefinitionCollector {
+    fn visit_link_definition(&mut self, definition: &LinkDefinition) {
+        self.definitions
+            .insert(definition.label.clone(), definition.url.clone());
+    }
+}
+
+#[derive(Default)]
+struct TaskCounter {
+    count: TaskCount,
+}
+
+impl Visitor for TaskCounter {
+    fn visit_list_item(&mut self, item: &ListItem) {
+        if let Some(checked) = item.checked {
+            self.count.total += 1;
+            self.count.completed += usize::from(checked);
+        }
+        visit::walk_blocks(self, &item.children);
+    }
+}
+
+/// See `Document::resolve_references`.
+struct UnsafeUrlDropper;
+
+impl UnsafeUrlDropper {
+    fn drop_unsafe(url: &mut String) {
+        if !is_safe_url(url) {
+            url.clear();
+        }
+    }
+}
+
+impl VisitorMut for UnsafeUrlDropper {
+    fn visit_link_definition_mut(&mut self, definition: &mut LinkDefinition) {
+        UnsafeUrlDropper::drop_unsafe(&mut definition.url);
+    }
+
+    fn visit_link_mut(&mut self, link: &mut Link) {
+        UnsafeUrlDropper::drop_unsafe(&mut link.destination);
+    }
+
+    fn visit_image_mut(&mut self, image: &mut Image) {
+        UnsafeUrlDropper::drop_unsafe(&mut image.destination);
+    }
+}
+
+pub(crate) struct ReferenceResolver<'a> {
+    pub definitions: &'a HashMap<String, String>,
+}
+
+impl<'a> VisitorMut for ReferenceResolver<'a> {
+    fn visit_inline_mut(&mut self, inline: &mut Inline) {
+        let text = match inline {
+            Inline::Link(i) if i.reference => match self.definitions.get(&i.destination) {
+                Some(url) => {
+                    i.destination = url.clone();
+                    i.reference = false;
+                    return;
+                }
+                None => format!("[{}][{}]", i.text, i.destination),
+            },
+            Inline::Image(i) if i.reference => match self.definitions.get(&i.destination) {
+                Some(url) => {
+                    i.destination = url.clone();
+                    i.reference = false;
+                    return;
+                }
+                None => format!("![{}][{}]", i.alt, i.destination),
+            },
+            _ => return visit::walk_inline_mut(self, inline),
+        };
+        let span = inline.span();
+        *inline = Inline::Text(Text { text, span });
+    }
+}
+
+impl Block {
+    pub fn from_tokens(tokens: Vec<LineToken>) -> Result<Vec<Block>, Error> {
+        tokens.into_iter().map(Block::from_token).collect()
+    }
+
+    pub fn from_token(token: LineToken) -> Result<Block, Error> {
+        let block = match token {
+            LineToken::HeaderToken(t) => Block::Heading(Heading {
+                level: t.level,
+                children: Inline::from_tokens(t.inline_tokens)?,
+                span: t.span,
+            }),
+            LineToken::Paragraph(t) => Block::Paragraph(Paragraph {
+                children: Inline::from_tokens(t.inline_tokens)?,
+                span: t.span,
+            }),
+            LineToken::CodeBlock(t) => Block::CodeBlock(CodeBlock {
+                info: t.info,
+                text: t.text,
+                span: t.span,
+            }),
+            LineToken::Quote(t) => Block::BlockQuote(BlockQuote {
+                children: Block::from_tokens(t.children)?,
+                span: t.span,
+            }),
+            LineToken::OrderedListBlock(t) => Block::List(List {
+                start: Some(t.start),
+                symbol: t.symbol,
+                tight: t.tight,
+                items: ListItem::from_tokens(t.lists)?,
+                span: t.span,
+            }),
+            LineToken::UnorderedListBlock(t) => Block::List(List {
+                start: None,
+                symbol: t.symbol,
+                tight: t.tight,
+                items: ListItem::from_tokens(t.lists)?,
+                span: t.span,
+            }),
+            LineToken::OrderedList(ref t) => Block::List(List {
+                start: Some(t.order),
+                symbol: t.symbol,
+                tight: true,
+                span: t.span,
+                items: vec![ListItem::from_token(token)?],
+            }),
+            LineToken::UnorderedList(ref t) => Block::List(List {
+                start: None,
+                symbol: t.symbol,
+                tight: true,
+                span: t.span,
+                items: vec![ListItem::from_token(token)?],
+            }),
+            LineToken::NoteToken(t) => Block::LinkDefinition(LinkDefinition {
+                label: t.name,
+                url: t.link,
+                span: t.span,
+            }),
+            LineToken::HorizontalRule(span) => Block::ThematicBreak(span),
+            LineToken::Table(t) => Block::Table(Table {
+                alignments: t.alignments,
+                head: TableRow::from_token(t.header)?,
+                rows: t
+                    .rows
+                    .into_iter()
+                    .map(TableRow::from_token)
+                    .collect::<Result<_, _>>()?,
+                span: t.span,
+            }),
+        };
+        Ok(block)
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Block::Heading(b) => b.span,
+            Block::Paragraph(b) => b.span,
+            Block::CodeBlock(b) => b.span,
+            Block::BlockQuote(b) => b.span,
+            Block::List(b) => b.span,
+            Block::LinkDefinition(b) => b.span,
+            Block::ThematicBreak(span) => *span,
+            Block::Table(b) => b.span,
+        }
+    }
+}
+
+impl TableRow {
+    pub fn from_token(token: tokenizer::TableRow) -> Result<TableRow, Error> {
+        let cells = token
+            .cells
+            .into_iter()
+            .map(|cell| {
+                Ok(TableCell {
+                    children: Inline::from_tokens(cell.inline_tokens)?,
+                    span: cell.span,
+                })
+            })
+            .collect::<Result<_, Error>>()?;
+        Ok(TableRow {
+            cells,
+            span: token.span,
+        })
+    }
+}
+
+impl ListItem {
+    pub fn from_tokens(tokens: Vec<LineToken>) -> Result<Vec<ListItem>, Error> {
+        tokens.into_iter().map(ListItem::from_token).collect()
+    }
+
+    pub fn from_token(token: LineToken) -> Result<ListItem, Error> {
+        let (checked, children, span) = match token {
+            LineToken::OrderedList(t) => (t.checked, t.children, t.span),
+            LineToken::UnorderedList(t) => (t.checked, t.children, t.span),
+            _ => return Err(Error::NotAListItem),
+        };
+        Ok(ListItem {
+            checked,
+            children: Block::from_tokens(children)?,
+            span,
+        })
+    }
+}
+
+impl Inline {
+    pub fn from_tokens(tokens: Vec<InlineToken>) -> Result<Vec<Inline>, Error> {
+        tokens.into_iter().map(Inline::from_token).collect()
+    }
+
+    pub fn from_token(token: InlineToken) -> Result<Inline, Error> {
+        let inline = match token {
+            InlineToken::TextToken(t) => Inline::Text(Text {
+                text: t.text,
+                span: t.span,
+            }),
+            InlineToken::SpecialToken(t) => match t.token {
+                '*' | '_' => Inline::Emphasis(Emphasis {
+                    symbol: t.token,
+                    children: Inline::from_tokens(t.inline_tokens)?,
+                    span: t.span,
+                }),
+                '`' => Inline::Code(Code {
+                    code: Inline::plain_text(&t.inline_tokens),
+                    span: t.span,
+                }),
+                c => return Err(Error::UnknownSymbol(c)),
+            },
+            InlineToken::DoubleSpecialToken(t) => match t.token {
+                '*' | '_' => Inline::Strong(Strong {
+                    symbol: t.token,
+                    children: Inline::from_tokens(t.inline_tokens)?,
+                    span: t.span,
+                }),
+                c => return Err(Error::UnknownSymbol(c)),
+            },
+            InlineToken::LinkToken(t) => Inline::Link(Link {
+                text: t.alt,
+                destination: t.link,
+                reference: t.need_note,
+                span: t.span,
+            }),
+            InlineToken::ImageToken(t) => Inline::Image(Image {
+                alt: t.alt,
+                destination: t.link,
+                reference: t.need_note,
+                span: t.span,
+            }),
+            InlineToken::StrikethroughToken(t) => Inline::Strikethrough(Strikethrough {
+                children: Inline::from_tokens(t.inline_tokens)?,
+                span: t.span,
+            }),
+            InlineToken::BreakToken(span) => Inline::HardBreak(span),
+            InlineToken::SoftBreak(span) => Inline::SoftBreak(span),
+        };
+        Ok(inline)
+    }
+
+    /// The text of `tokens` without any markup, as in the content of a code
+    /// span.
+    fn plain_text(tokens: &[InlineToken]) -> String {
+        let mut result = String::new();
+        for token in tokens {
+            match token {
+                InlineToken::TextToken(t) => result.push_str(&t.text),
+                InlineToken::SpecialToken(t) => {
+                    result.push_str(&Inline::plain_text(&t.inline_tokens))
+                }
+                InlineToken::DoubleSpecialToken(t) => {
+                    result.push_str(&Inline::plain_text(&t.inline_tokens))
+                }
+                InlineToken::StrikethroughToken(t) => {
+                    result.push_str(&Inline::plain_text(&t.inline_tokens))
+                }
+                InlineToken::LinkToken(t) => result.push_str(&t.alt),
+                InlineToken::ImageToken(t) => result.push_str(&t.alt),
+                InlineToken::BreakToken(_) | InlineToken::SoftBreak(_) => result.push('\n'),
+            }
+        }
+        result
+    }
+
+    pub fn span(&self) -> Span {
+        match self {
+            Inline::Text(i) => i.span,
+            Inline::Emphasis(i) => i.span,
+            Inline::Strong(i) => i.span,
+            Inline::Strikethrough(i) => i.span,
+            Inline::Code(i) => i.span,
+            Inline::Link(i) => i.span,
+            Inline::Image(i) => i.span,
+            Inline::HardBreak(span) | Inline::SoftBreak(span) => *span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(inline: &Inline) -> &str {
+        match inline {
+            Inline::Text(t) => &t.text,
+            _ => panic!("expected text, found {:?}", inline),
+        }
+    }
+
+    #[test]
+    fn test_blocks() {
+        let document = Document::parse("# a\n\nb\n\n> c\n\n```rs\nd\n```\n\n---\n[e]: f");
+        let children = &document.children;
+        assert_eq!(children.len(), 6);
+        match &children[0] {
+            Block::Heading(h) => {
+                assert_eq!(h.level, 1);
+                assert_eq!(text(&h.children[0]), "a");
+            }
+            _ => panic!(),
+        }
+        match &children[1] {
+            Block::Paragraph(p) => assert_eq!(text(&p.children[0]), "b"),
+            _ => panic!(),
+        }
+        match &children[2] {
+            Block::BlockQuote(q) => assert!(matches!(q.children[0], Block::Paragraph(_))),
+            _ => panic!(),
+        }
+        match &children[3] {
+            Block::CodeBlock(c) => {
+                assert_eq!(c.info.as_deref(), Some("rs"));
+                assert_eq!(c.text, "d");
+            }
+            _ => panic!(),
+        }
+        assert!(matches!(children[4], Block::ThematicBreak(_)));
+        match &children[5] {
+            Block::LinkDefinition(d) => {
+                assert_eq!(d.label, "e");
+                assert_eq!(d.url, " f");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_lists() {
+        let document = Document::parse("3) a\n4) b\n\n- c\n\n- d");
+        match &document.children[0] {
+            Block::List(list) => {
+                assert_eq!(list.start, Some(3));
+                assert_eq!(list.symbol, ')');
+                assert!(list.tight);
+                assert_eq!(list.items.len(), 2);
+                assert!(matches!(list.items[1].children[0], Block::Paragraph(_)));
+            }
+            _ => panic!(),
+        }
+        match &document.children[1] {
+            Block::List(list) => {
+                assert_eq!(list.start, None);
+                assert!(!list.tight);
+                assert_eq!(list.items.len(), 2);
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_inlines() {
+        let document = Document::parse("*a* __b__ `c` [d](e) ![f][g]\nh");
+        let children = match &document.children[0] {
+            Block::Paragraph(p) => &p.children,
+            _ => panic!(),
+        };
+        match &children[0] {
+            Inline::Emphasis(e) => {
+                assert_eq!(e.symbol, '*');
+                assert_eq!(text(&e.children[0]), "a");
+            }
+            _ => panic!(),
+        }
+        match &children[2] {
+            Inline::Strong(s) => assert_eq!(s.symbol, '_'),
+            _ => panic!(),
+        }
+        match &children[4] {
+            Inline::Code(c) => assert_eq!(c.code, "c"),
+            _ => panic!(),
+        }
+        match &children[6] {
+            Inline::Link(l) => {
+                assert_eq!(l.text, "d");
+                assert_eq!(l.destination, "e");
+                assert!(!l.reference);
+            }
+            _ => panic!(),
+        }
+        match &children[8] {
+            Inline::Image(i) => {
+                assert_eq!(i.alt, "f");
+                assert_eq!(i.destination, "g");
+                assert!(i.reference);
+            }
+            _ => panic!(),
+        }
+        assert!(matches!(children[9], Inline::SoftBreak(_)));
+        assert_eq!(children[10].span().start.line, 2);
+    }
+
+    #[test]
+    fn test_list_block_of_other_tokens() {
+        let mut tokens = Tokenizer::tokenizer("- a");
+        if let LineToken::UnorderedListBlock(block) = &mut tokens[0] {
+            block.lists.push(LineToken::HorizontalRule(Span::default()));
+        }
+        assert_eq!(Document::from_tokens(tokens), Err(Error::NotAListItem));
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let mut document = Document::parse("[a][x] ![b][y]\n\n[x]:/url");
+        let definitions = document.definitions();
+        assert_eq!(definitions["x"], "/url");
+        document.resolve_references(&definitions);
+        match &document.children[0] {
+            Block::Paragraph(p) => {
+                match &p.children[0] {
+                    Inline::Link(l) => {
+                        assert_eq!(l.destination, "/url");
+                        assert!(!l.reference);
+                    }
+                    i => panic!("expected a link, found {:?}", i),
+                }
+                assert_eq!(text(&p.children[2]), "![b][y]");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_drop_unsafe_urls() {
+        let mut document = Document::parse("[a](javascript:x) [b](/url)\n\n[c]: vbscript:y");
+        document.drop_unsafe_urls();
+        match &document.children[0] {
+            Block::Paragraph(p) => match (&p.children[0], &p.children[2]) {
+                (Inline::Link(a), Inline::Link(b)) => {
+                    assert_eq!(a.destination, "");
+                    assert_eq!(b.destination, "/url");
+                }
+                i => panic!("expected links, found {:?}", i),
+            },
+            _ => panic!(),
+        }
+        match &document.children[1] {
+            Block::LinkDefinition(d) => assert_eq!(d.url, ""),
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_task_count() {
+        let text = "- [ ] a\n- [x] b\n  1. [x] c\n  2. d\n\n> - [X] e";
+        let document = Document::try_parse_with(text, Extensions::gfm()).unwrap();
+        let expected = if cfg!(feature = "task-lists") {
+            TaskCount {
+                completed: 3,
+                total: 4,
+            }
+        } else {
+            TaskCount::default()
+        };
+        assert_eq!(document.task_count(), expected);
+        assert_eq!(Document::parse(text).task_count(), TaskCount::default());
+    }
+}
@@ -0,0 +1,323 @@
+//! Traversal of the node tree.
+//!
+//! Every `visit_*` method of `Visitor` and `VisitorMut` by default calls the
+//! `walk_*` function of the same name, which visits the node's children. Override
+//! the methods for the nodes you care about, and call `walk_*` from them to
+//! keep going into the children.
+use super::*;
+
+/// Visits the nodes of a tree by reference.
+pub trait Visitor {
+    fn visit_document(&mut self, document: &Document) {
+        walk_document(self, document);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_heading(&mut self, heading: &Heading) {
+        walk_inlines(self, &heading.children);
+    }
+
+    fn visit_paragraph(&mut self, paragraph: &Paragraph) {
+        walk_inlines(self, &paragraph.children);
+    }
+
+    fn visit_code_block(&mut self, _code_block: &CodeBlock) {}
+
+    fn visit_block_quote(&mut self, block_quote: &BlockQuote) {
+        walk_blocks(self, &block_quote.children);
+    }
+
+    fn visit_list(&mut self, list: &List) {
+        walk_list(self, list);
+    }
+
+    fn visit_list_item(&mut self, item: &ListItem) {
+        walk_blocks(self, &item.children);
+    }
+
+    fn visit_link_definition(&mut self, _definition: &LinkDefinition) {}
+
+    fn visit_thematic_break(&mut self, _span: Span) {}
+
+    fn visit_table(&mut self, table: &Table) {
+        walk_table(self, table);
+    }
+
+    fn visit_table_cell(&mut self, cell: &TableCell) {
+        walk_inlines(self, &cell.children);
+    }
+
+    fn visit_inline(&mut self, inline: &Inline) {
+        walk_inline(self, inline);
+    }
+
+    fn visit_text(&mut self, _text: &Text) {}
+
+    fn visit_emphasis(&mut self, emphasis: &Emphasis) {
+        walk_inlines(self, &emphasis.children);
+    }
+
+    fn visit_strong(&mut self, strong: &Strong) {
+        walk_inlines(self, &strong.children);
+    }
+
+    fn visit_strikethrough(&mut self, strikethrough: &Strikethrough) {
+        walk_inlines(self, &strikethrough.children);
+    }
+
+    fn visit_code(&mut self, _code: &Code) {}
+
+    fn visit_link(&mut self, _link: &Link) {}
+
+    fn visit_image(&mut self, _image: &Image) {}
+
+    fn visit_hard_break(&mut self, _span: Span) {}
+
+    fn visit_soft_break(&mut self, _span: Span) {}
+}
+
+pub fn walk_document<V: Visitor + ?Sized>(visitor: &mut V, document: &Document) {
+    walk_blocks(visitor, &document.children);
+}
+
+pub fn walk_blocks<V: Visitor + ?Sized>(visitor: &mut V, blocks: &[Block]) {
+    for block in blocks {
+        visitor.visit_block(block);
+    }
+}
+
+/// Calls the `visit_*` method for the kind of `block`.
+pub fn walk_block<V: Visitor + ?Sized>(visitor: &mut V, block: &Block) {
+    match block {
+        Block::Heading(b) => visitor.visit_heading(b),
+        Block::Paragraph(b) => visitor.visit_paragraph(b),
+        Block::CodeBlock(b) => visitor.visit_code_block(b),
+        Block::BlockQuote(b) => visitor.visit_block_quote(b),
+        Block::List(b) => visitor.visit_list(b),
+        Block::LinkDefinition(b) => visitor.visit_link_definition(b),
+        Block::ThematicBreak(span) => visitor.visit_thematic_break(*span),
+        Block::Table(b) => visitor.visit_table(b),
+    }
+}
+
+pub fn walk_list<V: Visitor + ?Sized>(visitor: &mut V, list: &List) {
+    for item in &list.items {
+        visitor.visit_list_item(item);
+    }
+}
+
+/// Visits the cells of the head of `table`, then those of its rows.
+pub fn walk_table<V: Visitor + ?Sized>(visitor: &mut V, table: &Table) {
+    for row in std::iter::once(&table.head).chain(&table.rows) {
+        for cell in &row.cells {
+            visitor.visit_table_cell(cell);
+        }
+    }
+}
+
+pub fn walk_inlines<V: Visitor + ?Sized>(visitor: &mut V, inlines: &[Inline]) {
+    for inline in inlines {
+        visitor.visit_inline(inline);
+    }
+}
+
+/// Calls the `visit_*` method for the kind of `inline`.
+pub fn walk_inline<V: Visitor + ?Sized>(visitor: &mut V, inline: &Inline) {
+    match inline {
+        Inline::Text(i) => visitor.visit_text(i),
+        Inline::Emphasis(i) => visitor.visit_emphasis(i),
+        Inline::Strong(i) => visitor.visit_strong(i),
+        Inline::Strikethrough(i) => visitor.visit_strikethrough(i),
+        Inline::Code(i) => visitor.visit_code(i),
+        Inline::Link(i) => visitor.visit_link(i),
+        Inline::Image(i) => visitor.visit_image(i),
+        Inline::HardBreak(span) => visitor.visit_hard_break(*span),
+        Inline::SoftBreak(span) => visitor.visit_soft_break(*span),
+    }
+}
+
+/// Visits the nodes of a tree by mutable reference, to change them in place.
+pub trait VisitorMut {
+    fn visit_document_mut(&mut self, document: &mut Document) {
+        walk_document_mut(self, document);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_heading_mut(&mut self, heading: &mut Heading) {
+        walk_inlines_mut(self, &mut heading.children);
+    }
+
+    fn visit_paragraph_mut(&mut self, paragraph: &mut Paragraph) {
+        walk_inlines_mut(self, &mut paragraph.children);
+    }
+
+    fn visit_code_block_mut(&mut self, _code_block: &mut CodeBlock) {}
+
+    fn visit_block_quote_mut(&mut self, block_quote: &mut BlockQuote) {
+        walk_blocks_mut(self, &mut block_quote.children);
+    }
+
+    fn visit_list_mut(&mut self, list: &mut List) {
+        walk_list_mut(self, list);
+    }
+
+    fn visit_list_item_mut(&mut self, item: &mut ListItem) {
+        walk_blocks_mut(self, &mut item.children);
+    }
+
+    fn visit_link_definition_mut(&mut self, _definition: &mut LinkDefinition) {}
+
+    fn visit_thematic_break_mut(&mut self, _span: &mut Span) {}
+
+    fn visit_table_mut(&mut self, table: &mut Table) {
+        walk_table_mut(self, table);
+    }
+
+    fn visit_table_cell_mut(&mut self, cell: &mut TableCell) {
+        walk_inlines_mut(self, &mut cell.children);
+    }
+
+    fn visit_inline_mut(&mut self, inline: &mut Inline) {
+        walk_inline_mut(self, inline);
+    }
+
+    fn visit_text_mut(&mut self, _text: &mut Text) {}
+
+    fn visit_emphasis_mut(&mut self, emphasis: &mut Emphasis) {
+        walk_inlines_mut(self, &mut emphasis.children);
+    }
+
+    fn visit_strong_mut(&mut self, strong: &mut Strong) {
+        walk_inlines_mut(self, &mut strong.children);
+    }
+
+    fn visit_strikethrough_mut(&mut self, strikethrough: &mut Strikethrough) {
+        walk_inlines_mut(self, &mut strikethrough.children);
+    }
+
+    fn visit_code_mut(&mut self, _code: &mut Code) {}
+
+    fn visit_link_mut(&mut self, _link: &mut Link) {}
+
+    fn visit_image_mut(&mut self, _image: &mut Image) {}
+
+    fn visit_hard_break_mut(&mut self, _span: &mut Span) {}
+
+    fn visit_soft_break_mut(&mut self, _span: &mut Span) {}
+}
+
+pub fn walk_document_mut<V: VisitorMut + ?Sized>(visitor: &mut V, document: &mut Document) {
+    walk_blocks_mut(visitor, &mut document.children);
+}
+
+pub fn walk_blocks_mut<V: VisitorMut + ?Sized>(visitor: &mut V, blocks: &mut [Block]) {
+    for block in blocks {
+        visitor.visit_block_mut(block);
+    }
+}
+
+/// Calls the `visit_*_mut` method for the kind of `block`.
+pub fn walk_block_mut<V: VisitorMut + ?Sized>(visitor: &mut V, block: &mut Block) {
+    match block {
+        Block::Heading(b) => visitor.visit_heading_mut(b),
+        Block::Paragraph(b) => visitor.visit_paragraph_mut(b),
+        Block::CodeBlock(b) => visitor.visit_code_block_mut(b),
+        Block::BlockQuote(b) => visitor.visit_block_quote_mut(b),
+        Block::List(b) => visitor.visit_list_mut(b),
+        Block::LinkDefinition(b) => visitor.visit_link_definition_mut(b),
+        Block::ThematicBreak(span) => visitor.visit_thematic_break_mut(span),
+        Block::Table(b) => visitor.visit_table_mut(b),
+    }
+}
+
+pub fn walk_list_mut<V: VisitorMut + ?Sized>(visitor: &mut V, list: &mut List) {
+    for item in &mut list.items {
+        visitor.visit_list_item_mut(item);
+    }
+}
+
+pub fn walk_table_mut<V: VisitorMut + ?Sized>(visitor: &mut V, table: &mut Table) {
+    for row in std::iter::once(&mut table.head).chain(&mut table.rows) {
+        for cell in &mut row.cells {
+            visitor.visit_table_cell_mut(cell);
+        }
+    }
+}
+
+pub fn walk_inlines_mut<V: VisitorMut + ?Sized>(visitor: &mut V, inlines: &mut [Inline]) {
+    for inline in inlines {
+        visitor.visit_inline_mut(inline);
+    }
+}
+
+/// Calls the `visit_*_mut` method for the kind of `inline`.
+pub fn walk_inline_mut<V: VisitorMut + ?Sized>(visitor: &mut V, inline: &mut Inline) {
+    match inline {
+        Inline::Text(i) => visitor.visit_text_mut(i),
+        Inline::Emphasis(i) => visitor.visit_emphasis_mut(i),
+        Inline::Strong(i) => visitor.visit_strong_mut(i),
+        Inline::Strikethrough(i) => visitor.visit_strikethrough_mut(i),
+        Inline::Code(i) => visitor.visit_code_mut(i),
+        Inline::Link(i) => visitor.visit_link_mut(i),
+        Inline::Image(i) => visitor.visit_image_mut(i),
+        Inline::HardBreak(span) => visitor.visit_hard_break_mut(span),
+        Inline::SoftBreak(span) => visitor.visit_soft_break_mut(span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct LinkCollector {
+        destinations: Vec<String>,
+        headings: usize,
+    }
+
+    impl Visitor for LinkCollector {
+        fn visit_heading(&mut self, heading: &Heading) {
+            self.headings += 1;
+            walk_inlines(self, &heading.children);
+        }
+
+        fn visit_link(&mut self, link: &Link) {
+            self.destinations.push(link.destination.clone());
+        }
+    }
+
+    #[test]
+    fn test_visitor_reaches_nested_nodes() {
+        let document = Document::parse("# [a](b)\n\n> - *[c](d)*\n>   1. **[e](f)**");
+        let mut collector = LinkCollector::default();
+        collector.visit_document(&document);
+        assert_eq!(collector.headings, 1);
+        assert_eq!(collector.destinations, vec!["b", "d", "f"]);
+    }
+
+    struct Shout;
+
+    impl VisitorMut for Shout {
+        fn visit_text_mut(&mut self, text: &mut Text) {
+            text.text = text.text.to_uppercase();
+        }
+
+        fn visit_code_block_mut(&mut self, code_block: &mut CodeBlock) {
+            code_block.text = code_block.text.to_uppercase();
+        }
+    }
+
+    #[test]
+    fn test_visitor_mut_changes_nodes() {
+        let mut document = Document::parse("a *b*\n\n- c\n\n    d");
+        Shout.visit_document_mut(&mut document);
+        let expected = Document::parse("A *B*\n\n- C\n\n    D");
+        assert_eq!(document, expected);
+    }
+}
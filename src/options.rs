@@ -0,0 +1,123 @@
+//! Options of the tokenizer and the renderers.
+
+/// The GitHub Flavored Markdown extensions to the CommonMark syntax. All of
+/// them are off by default; `Extensions::gfm()` turns them all on.
+///
+/// Each extension is only read if the crate was built with its cargo feature,
+/// such as `tables`; the `gfm` default feature builds all of them.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Extensions {
+    /// Tables of `|`-separated cells, with a delimiter row under the header.
+    pub tables: bool,
+    /// List items starting with `[ ]` or `[x]`, shown as checkboxes.
+    pub task_lists: bool,
+    /// `~text~` and `~~text~~`, shown struck through.
+    pub strikethrough: bool,
+    /// URLs, `www.` addresses and email addresses turned into links without
+    /// `<` and `>` around them.
+    pub autolinks: bool,
+}
+
+impl Extensions {
+    /// Every extension.
+    pub fn gfm() -> Self {
+        Extensions {
+            tables: true,
+            task_lists: true,
+            strikethrough: true,
+            autolinks: true,
+        }
+    }
+}
+
+/// How a line break inside a paragraph is written.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SoftBreakStyle {
+    /// As a newline, which browsers show as a space.
+    #[default]
+    Newline,
+    /// As a space.
+    Space,
+    /// As a hard line break, `<br>`.
+    Hard,
+}
+
+/// Options of `Parser`. The tokenizer reads `extensions` and the renderers
+/// read the rest. The defaults give plain CommonMark as HTML.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ParserOptions {
+    pub extensions: Extensions,
+    /// Safe mode: links and images whose URL could run code, with a
+    /// `javascript:`, `vbscript:`, `file:` or `data:` scheme, get an empty URL
+    /// instead. `data:` URLs of PNG, GIF, JPEG and WebP images are kept. Raw
+    /// HTML is always escaped, whatever this is.
+    pub safe: bool,
+    /// Writes empty elements the XHTML way, such as `<br />` for `<br>`.
+    pub xhtml: bool,
+    pub soft_break: SoftBreakStyle,
+    /// Gives every block element a `data-sourcepos` attribute with the lines
+    /// and columns of its source text, written as
+    /// `start_line:start_column-end_line:end_column` like cmark does.
+    pub source_positions: bool,
+}
+
+impl ParserOptions {
+    /// The defaults, with every GFM extension on.
+    pub fn gfm() -> Self {
+        ParserOptions {
+            extensions: Extensions::gfm(),
+            ..ParserOptions::default()
+        }
+    }
+}
+
+/// Whether safe mode keeps `url`. See `ParserOptions::safe`.
+///
+/// The scheme is read the way browsers read it: without any tab or newline,
+/// and after the control characters and spaces at the start.
+pub fn is_safe_url(url: &str) -> bool {
+    let url: String = url
+        .chars()
+        .filter(|c| !matches!(c, '\t' | '\n' | '\r'))
+        .collect();
+    let url = url.trim_start_matches(|c| c <= ' ').to_ascii_lowercase();
+    if let Some(kind) = url.strip_prefix("data:image/") {
+        return ["png", "gif", "jpeg", "webp"].iter().any(|image| {
+            kind.starts_with(image) && matches!(kind[image.len()..].chars().next(), Some(';' | ','))
+        });
+    }
+    if url.starts_with("data:") {
+        return false;
+    }
+    !["javascript:", "vbscript:", "file:"]
+        .iter()
+        .any(|scheme| url.starts_with(scheme))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_safe_url() {
+        assert!(is_safe_url("http://a.com"));
+        assert!(is_safe_url("/javascript:"));
+        assert!(is_safe_url("data:image/png;base64,AAAA"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url(" JavaScript:alert(1)"));
+        assert!(!is_safe_url("vbscript:x"));
+        assert!(!is_safe_url("file:///etc/passwd"));
+        assert!(!is_safe_url("data:text/html,<script>"));
+        assert!(!is_safe_url("java\tscript:alert(1)"));
+        assert!(!is_safe_url("java\nscript:alert(1)"));
+        assert!(!is_safe_url("\r\njavascript:alert(1)"));
+        assert!(!is_safe_url("\u{1}javascript:alert(1)"));
+        assert!(!is_safe_url("\u{0} \u{1f}vbscript:x"));
+        assert!(!is_safe_url("da\nta:text/html,x"));
+    }
+}
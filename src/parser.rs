@@ -1,3 +1,7 @@
+use super::html::{self, HtmlRenderer};
+use super::node::{Block, Document, Inline, ListItem, ReferenceResolver, VisitorMut};
+use super::options::ParserOptions;
+use super::render::{self, Output, Renderer};
 pub use super::tokenizer::inline_token::{
     DoubleSpecialToken, ImageToken, InlineToken, LinkToken, SpecialToken, TextToken,
 };
@@ -6,172 +10,307 @@ pub use super::tokenizer::line_token::{
     UnorderedListBlock,
 };
 pub use super::tokenizer::Tokenizer;
+use super::Error;
 
 use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, Write};
+
+/// A problem found in a document that didn't stop it from being rendered.
+#[derive(Debug, PartialEq)]
+pub enum Diagnostic {
+    /// A `[text][label]` link or image whose label has no `[label]: url`
+    /// note. It is rendered as literal text.
+    UndefinedReference(String),
+}
 
 pub struct Parser {
     notes: HashMap<String, String>,
+    collect_diagnostics: bool,
+    diagnostics: Vec<Diagnostic>,
+    options: ParserOptions,
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
 }
 
 impl Parser {
     pub fn new() -> Self {
+        Parser::with_options(ParserOptions::default())
+    }
+
+    pub fn with_options(options: ParserOptions) -> Self {
         let notes = HashMap::new();
-        Self { notes }
+        Self {
+            notes,
+            collect_diagnostics: false,
+            diagnostics: Vec::new(),
+            options,
+        }
+    }
+
+    pub fn options(&self) -> &ParserOptions {
+        &self.options
+    }
+
+    pub fn options_mut(&mut self) -> &mut ParserOptions {
+        &mut self.options
+    }
+
+    /// Makes `parse` record problems in the document, such as undefined link
+    /// references, which can then be read with `diagnostics`.
+    pub fn set_collect_diagnostics(&mut self, collect: bool) {
+        self.collect_diagnostics = collect;
+    }
+
+    /// The problems found by the last call to `parse`. Always empty unless
+    /// `set_collect_diagnostics(true)` was called.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
     }
 
-    pub fn inline_parse(&self, token: &InlineToken) -> String {
+    /// Makes `parse` give every block element a `data-sourcepos` attribute.
+    /// Same as setting `options_mut().source_positions`.
+    pub fn set_source_positions(&mut self, enabled: bool) {
+        self.options.source_positions = enabled;
+    }
+
+    /// Escapes the characters that are special in HTML text and attribute
+    /// values: `&`, `<`, `>` and `"`.
+    pub fn escape_html(text: &str) -> String {
+        html::escape_html(text)
+    }
+
+    /// The renderer of `parse`.
+    fn html_renderer(&self) -> HtmlRenderer {
+        HtmlRenderer::with_options(self.options)
+    }
+
+    /// Resolves reference links and images with the notes of the last
+    /// document.
+    fn resolver(&self) -> ReferenceResolver<'_> {
+        ReferenceResolver {
+            definitions: &self.notes,
+        }
+    }
+
+    pub fn inline_parse(&self, token: &InlineToken) -> Result<String, Error> {
+        let mut inline = Inline::from_token(token.clone())?;
+        self.resolver().visit_inline_mut(&mut inline);
         let mut result = String::new();
+        let output = &mut Output::new(&mut result);
+        render::render_inline(&mut self.html_renderer(), output, &inline)?;
+        Ok(result)
+    }
+
+    /// Returns the URL of a link or image. Links that need a note are looked up
+    /// by label, and `None` means the note is missing.
+    fn resolve_link<'a>(&'a self, link: &'a String, need_note: bool) -> Option<&'a String> {
+        if need_note {
+            self.notes.get(link)
+        } else {
+            Some(link)
+        }
+    }
+
+    pub fn line_parse(&self, token: &LineToken) -> Result<String, Error> {
         match token {
-            InlineToken::TextToken(token) => {
-                result.push_str(&token.text);
-            }
-            InlineToken::SpecialToken(token) => {
-                let tokens = &token.inline_tokens;
-                match token.token {
-                    '*' | '_' => {
-                        result.push_str(&"<em>");
-                        for t in tokens {
-                            result.push_str(self.inline_parse(t).as_str());
-                        }
-                        result.push_str(&"</em>");
-                    }
-                    '`' => {
-                        result.push_str(&"<code>");
-                        for t in tokens {
-                            result.push_str(self.inline_parse(t).as_str());
-                        }
-                        result.push_str("</code>")
-                    }
-                    _ => panic!(),
-                };
-            }
-            InlineToken::DoubleSpecialToken(token) => {
-                let tokens = &token.inline_tokens;
-                match token.token {
-                    '*' | '_' => {
-                        result.push_str(&"<strong>");
-                        for t in tokens {
-                            result.push_str(self.inline_parse(t).as_str());
-                        }
-                        result.push_str(&"</strong>");
-                    }
-                    _ => panic!(),
-                }
-            }
-            InlineToken::ImageToken(token) => {
-                let link: &String;
-                if token.need_note {
-                    link = &self.notes[&token.link];
-                } else {
-                    link = &token.link;
-                }
-                result.push_str(format!("<img src=\"{}\" alt=\"{}\">", link, token.alt).as_str());
-            }
-            InlineToken::LinkToken(token) => {
-                let link: &String;
-                if token.need_note {
-                    link = &self.notes[&token.link];
-                } else {
-                    link = &token.link;
-                }
-                result.push_str(format!("<a href=\"{}\">{}</a>", link, token.alt).as_str());
+            LineToken::OrderedList(_) | LineToken::UnorderedList(_) => {
+                self.list_item_parse(token, true)
             }
-            InlineToken::BreakToken => {
-                result.push_str("<br>");
+            _ => {
+                let mut block = Block::from_token(token.clone())?;
+                self.resolver().visit_block_mut(&mut block);
+                let mut result = String::new();
+                let output = &mut Output::new(&mut result);
+                render::render_block(&mut self.html_renderer(), output, &block, false)?;
+                Ok(result)
             }
-        };
-        result
+        }
     }
 
-    pub fn line_parse(&self, token: &LineToken) -> String {
-        let mut result: String = String::new();
+    /// Renders a list item. Items of a tight list show their paragraphs
+    /// without the surrounding `<p>`.
+    pub fn list_item_parse(&self, token: &LineToken, tight: bool) -> Result<String, Error> {
         match token {
-            LineToken::HeaderToken(token) => {
-                let level = token.level;
-                let tokens = &token.inline_tokens;
-                result.push_str(&format!("<h{}>", level));
-                for t in tokens {
-                    result.push_str(self.inline_parse(t).as_str());
-                }
-                result.push_str(&format!("</h{}>", level));
+            LineToken::OrderedList(_) | LineToken::UnorderedList(_) => {
+                let mut item = ListItem::from_token(token.clone())?;
+                self.resolver().visit_list_item_mut(&mut item);
+                let mut result = String::new();
+                let output = &mut Output::new(&mut result);
+                self.html_renderer().list_item(output, &item, tight)?;
+                Ok(result)
             }
-            LineToken::Paragraph(token) => {
-                result.push_str(&"<p>\n");
-                for t in &token.inline_tokens {
-                    result.push_str(self.inline_parse(t).as_str());
+            _ => self.line_parse(token),
+        }
+    }
+
+    pub fn extract_notes(tokens: &[LineToken]) -> HashMap<String, String> {
+        let mut result = HashMap::new();
+        for token in tokens {
+            match token {
+                LineToken::NoteToken(t) => {
+                    let name = t.name.clone();
+                    let link = t.link.clone();
+                    result.insert(name, link);
                 }
-                result.push_str(&"\n</p>");
+                LineToken::Quote(t) => result.extend(Parser::extract_notes(&t.children)),
+                LineToken::OrderedListBlock(t) => result.extend(Parser::extract_notes(&t.lists)),
+                LineToken::UnorderedListBlock(t) => result.extend(Parser::extract_notes(&t.lists)),
+                LineToken::OrderedList(t) => result.extend(Parser::extract_notes(&t.children)),
+                LineToken::UnorderedList(t) => result.extend(Parser::extract_notes(&t.children)),
+                _ => (),
             }
-            LineToken::CodeBlock(token) => {
-                result.push_str("<pre><code>\n");
-                result.push_str(&token.text);
-                result.push_str("\n</code></pre>");
-            }
-            LineToken::Quote(token) => {
-                result.push_str("<blockquote><p>\n");
-                for t in &token.inline_tokens {
-                    result.push_str(&self.inline_parse(t));
+        }
+        result
+    }
+
+    /// Pushes a diagnostic for every link or image in `tokens` that refers to
+    /// a note that doesn't exist.
+    fn find_undefined_references(&self, tokens: &[InlineToken], result: &mut Vec<Diagnostic>) {
+        for token in tokens {
+            match token {
+                InlineToken::LinkToken(t) if self.resolve_link(&t.link, t.need_note).is_none() => {
+                    result.push(Diagnostic::UndefinedReference(t.link.clone()))
                 }
-                result.push_str("\n</p></blockquote>")
-            }
-            LineToken::UnorderedListBlock(token) => {
-                result.push_str("<ul>\n");
-                for t in &token.lists {
-                    result.push_str(&self.line_parse(t));
+                InlineToken::ImageToken(t) if self.resolve_link(&t.link, t.need_note).is_none() => {
+                    result.push(Diagnostic::UndefinedReference(t.link.clone()))
                 }
-                result.push_str("</ul>")
-            }
-            LineToken::OrderedListBlock(token) => {
-                let start = token.start;
-                result.push_str(&format!("<ol start=\"{}\">\n", start));
-                for t in &token.lists {
-                    result.push_str(&self.line_parse(t));
+                InlineToken::SpecialToken(t) => {
+                    self.find_undefined_references(&t.inline_tokens, result)
                 }
-                result.push_str("</ol>")
-            }
-            LineToken::OrderedList(token) => {
-                result.push_str("<li>");
-                for t in &token.inline_tokens {
-                    result.push_str(&self.inline_parse(t));
+                InlineToken::DoubleSpecialToken(t) => {
+                    self.find_undefined_references(&t.inline_tokens, result)
                 }
-                result.push_str("</li>")
-            }
-            LineToken::UnorderedList(token) => {
-                result.push_str("<li>");
-                for t in &token.inline_tokens {
-                    result.push_str(&self.inline_parse(t));
+                InlineToken::StrikethroughToken(t) => {
+                    self.find_undefined_references(&t.inline_tokens, result)
                 }
-                result.push_str("</li>")
-            }
-            LineToken::HorizontalRule => {
-                result.push_str("<hr>");
+                _ => (),
             }
-            LineToken::NoteToken(_) => (),
         }
-        result.push_str("\n");
-        result
     }
 
-    pub fn extract_notes(tokens: &Vec<LineToken>) -> HashMap<String, String> {
-        let mut result = HashMap::new();
+    /// Collects the diagnostics of `tokens` and of every block nested in them.
+    fn check_lines(&self, tokens: &[LineToken], result: &mut Vec<Diagnostic>) {
         for token in tokens {
-            if let LineToken::NoteToken(t) = token {
-                let name = t.name.clone();
-                let link = t.link.clone();
-                result.insert(name, link);
+            match token {
+                LineToken::HeaderToken(t) => {
+                    self.find_undefined_references(&t.inline_tokens, result)
+                }
+                LineToken::Paragraph(t) => self.find_undefined_references(&t.inline_tokens, result),
+                LineToken::Quote(t) => self.check_lines(&t.children, result),
+                LineToken::OrderedListBlock(t) => self.check_lines(&t.lists, result),
+                LineToken::UnorderedListBlock(t) => self.check_lines(&t.lists, result),
+                LineToken::OrderedList(t) => self.check_lines(&t.children, result),
+                LineToken::UnorderedList(t) => self.check_lines(&t.children, result),
+                LineToken::Table(t) => {
+                    for row in std::iter::once(&t.header).chain(&t.rows) {
+                        for cell in &row.cells {
+                            self.find_undefined_references(&cell.inline_tokens, result);
+                        }
+                    }
+                }
+                _ => (),
             }
         }
-        result
     }
 
+    /// Renders `text` as HTML. Never panics. `try_parse` only fails on tokens
+    /// the tokenizer doesn't build, so this returns an empty string only if
+    /// there is a bug.
     pub fn parse(&mut self, text: &str) -> String {
+        self.try_parse(text).unwrap_or_default()
+    }
+
+    pub fn try_parse(&mut self, text: &str) -> Result<String, Error> {
+        let mut renderer = self.html_renderer();
+        self.try_parse_with(text, &mut renderer)
+    }
+
+    /// Renders `text` with `renderer` instead of as HTML. The extensions of the
+    /// parser's options decide the syntax, and its safe mode empties unsafe
+    /// URLs before `renderer` sees them; the renderer's own `options()` decide
+    /// how the rest is written. Never panics, like `parse`.
+    pub fn parse_with<R: Renderer>(&mut self, text: &str, renderer: &mut R) -> String {
+        self.try_parse_with(text, renderer).unwrap_or_default()
+    }
+
+    /// Renders `text` with `renderer`, after resolving its reference links and
+    /// images with its notes.
+    pub fn try_parse_with<R: Renderer>(
+        &mut self,
+        text: &str,
+        renderer: &mut R,
+    ) -> Result<String, Error> {
+        let document = self.document(text)?;
         let mut result = String::new();
-        let tokens = Tokenizer::tokenizer(text);
+        renderer.render_fmt(&mut result, &document)?;
+        Ok(result)
+    }
+
+    /// Renders `text` as HTML straight to `writer`, without building the
+    /// whole string first. Fails with the first error of `writer`.
+    pub fn render_fmt<W: fmt::Write>(&mut self, text: &str, writer: &mut W) -> Result<(), Error> {
+        let document = self.document(text)?;
+        self.html_renderer().render_fmt(writer, &document)?;
+        Ok(())
+    }
+
+    /// Renders `text` as HTML to a file, socket or any other `io::Write`, as
+    /// it goes. The output is buffered, so `writer` needn't be. Errors of
+    /// `try_parse` are given as `io::ErrorKind::InvalidData`.
+    pub fn render_to<W: io::Write>(&mut self, text: &str, writer: W) -> io::Result<()> {
+        let document = self
+            .document(text)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        let mut sink = IoSink {
+            writer: io::BufWriter::new(writer),
+            error: None,
+        };
+        match self.html_renderer().render_fmt(&mut sink, &document) {
+            Ok(()) => sink.writer.flush(),
+            Err(e) => Err(sink
+                .error
+                .unwrap_or_else(|| io::Error::new(io::ErrorKind::Other, Error::Fmt(e)))),
+        }
+    }
+
+    /// Builds the tree of `text` with its reference links and images resolved,
+    /// keeping its notes and diagnostics.
+    fn document(&mut self, text: &str) -> Result<Document, Error> {
+        let tokens = Tokenizer::try_tokenize_with(text, self.options.extensions)?;
         self.notes = Parser::extract_notes(&tokens);
-        for token in tokens {
-            result.push_str(self.line_parse(&token).as_str());
+        self.diagnostics.clear();
+        if self.collect_diagnostics {
+            let mut diagnostics = Vec::new();
+            self.check_lines(&tokens, &mut diagnostics);
+            self.diagnostics = diagnostics;
         }
-        result
+        let mut document = Document::from_tokens(tokens)?;
+        document.resolve_references(&self.notes);
+        if self.options.safe {
+            document.drop_unsafe_urls();
+        }
+        Ok(document)
+    }
+}
+
+/// Lets a `Renderer` write to an `io::Write`, keeping the I/O error that
+/// `fmt::Error` can't carry.
+struct IoSink<W: io::Write> {
+    writer: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> fmt::Write for IoSink<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
     }
 }
 
@@ -181,12 +320,23 @@ mod test {
     pub use super::*;
     use crate::tokenizer::CodeBlock;
     use crate::tokenizer::LinkToken;
+    use crate::tokenizer::Span;
 
     pub fn text_token_factory(text: String) -> InlineToken {
-        let t = TextToken { text };
+        let t = TextToken {
+            text,
+            span: Span::default(),
+        };
         InlineToken::TextToken(t)
     }
 
+    pub fn paragraph_factory(text: &str) -> LineToken {
+        LineToken::Paragraph(Paragraph {
+            inline_tokens: vec![text_token_factory(text.to_string())],
+            span: Span::default(),
+        })
+    }
+
     pub fn special_token_factory(token: char, text: String) -> InlineToken {
         let text_token = text_token_factory(text);
         let t = SpecialToken::new(token, vec![text_token]);
@@ -204,9 +354,10 @@ mod test {
         let parser = Parser::new();
         let t = TextToken {
             text: String::from("this is a test"),
+            span: Span::default(),
         };
         let token = InlineToken::TextToken(t);
-        let result = parser.inline_parse(&token);
+        let result = parser.inline_parse(&token).unwrap();
         assert_eq!("this is a test", result);
     }
 
@@ -214,7 +365,7 @@ mod test {
     fn test_italic_inline_parser() {
         let parser = Parser::new();
         let tokens = special_token_factory('*', String::from("this is a test"));
-        let result = parser.inline_parse(&tokens);
+        let result = parser.inline_parse(&tokens).unwrap();
         assert_eq!("<em>this is a test</em>", result);
     }
 
@@ -222,14 +373,16 @@ mod test {
     fn test_paragraph_parser() {
         let text_token = TextToken {
             text: String::from("this is a test"),
+            span: Span::default(),
         };
         let paragraph = Paragraph {
             inline_tokens: vec![InlineToken::TextToken(text_token)],
+            span: Span::default(),
         };
         let parser = Parser::new();
 
         let token = LineToken::Paragraph(paragraph);
-        let result = parser.line_parse(&token);
+        let result = parser.line_parse(&token).unwrap();
         assert_eq!("<p>\nthis is a test\n</p>\n", result);
     }
 
@@ -239,17 +392,21 @@ mod test {
             inline_tokens: vec![
                 InlineToken::TextToken(TextToken {
                     text: String::from("this is a test"),
+                    span: Span::default(),
                 }),
                 InlineToken::SpecialToken(SpecialToken {
                     token: '*',
                     inline_tokens: vec![InlineToken::TextToken(TextToken {
                         text: String::from("another test"),
+                        span: Span::default(),
                     })],
+                    span: Span::default(),
                 }),
             ],
+            span: Span::default(),
         };
         let parser = Parser::new();
-        let result = parser.line_parse(&LineToken::Paragraph(token));
+        let result = parser.line_parse(&LineToken::Paragraph(token)).unwrap();
         assert_eq!("<p>\nthis is a test<em>another test</em>\n</p>\n", result);
     }
 
@@ -257,7 +414,7 @@ mod test {
     fn test_strong_inline_parser() {
         let parser = Parser::new();
         let token = double_special_token_factory('*', String::from("this is a test"));
-        let result = parser.inline_parse(&token);
+        let result = parser.inline_parse(&token).unwrap();
         assert_eq!("<strong>this is a test</strong>", result);
     }
 
@@ -268,9 +425,11 @@ mod test {
             token: '`',
             inline_tokens: vec![InlineToken::TextToken(TextToken {
                 text: String::from("this is a test"),
+                span: Span::default(),
             })],
+            span: Span::default(),
         });
-        let result = parser.inline_parse(&token);
+        let result = parser.inline_parse(&token).unwrap();
         assert_eq!("<code>this is a test</code>", result);
     }
 
@@ -281,16 +440,20 @@ mod test {
             inline_tokens: vec![
                 InlineToken::TextToken(TextToken {
                     text: String::from("this is a test"),
+                    span: Span::default(),
                 }),
                 InlineToken::DoubleSpecialToken(DoubleSpecialToken {
                     token: '*',
                     inline_tokens: vec![InlineToken::TextToken(TextToken {
                         text: String::from("another test"),
+                        span: Span::default(),
                     })],
+                    span: Span::default(),
                 }),
             ],
+            span: Span::default(),
         });
-        let result = parser.line_parse(&token);
+        let result = parser.line_parse(&token).unwrap();
         assert_eq!(
             "<p>\nthis is a test<strong>another test</strong>\n</p>\n",
             result
@@ -304,22 +467,28 @@ mod test {
             inline_tokens: vec![
                 InlineToken::TextToken(TextToken {
                     text: String::from("this is a test"),
+                    span: Span::default(),
                 }),
                 InlineToken::DoubleSpecialToken(DoubleSpecialToken {
                     token: '*',
                     inline_tokens: vec![InlineToken::TextToken(TextToken {
                         text: String::from("another test"),
+                        span: Span::default(),
                     })],
+                    span: Span::default(),
                 }),
                 InlineToken::SpecialToken(SpecialToken {
                     token: '*',
                     inline_tokens: vec![InlineToken::TextToken(TextToken {
                         text: String::from("another test"),
+                        span: Span::default(),
                     })],
+                    span: Span::default(),
                 }),
             ],
+            span: Span::default(),
         });
-        let result = parser.line_parse(&token);
+        let result = parser.line_parse(&token).unwrap();
         assert_eq!(
             "<p>\nthis is a test<strong>another test</strong><em>another test</em>\n</p>\n",
             result
@@ -331,10 +500,77 @@ mod test {
         let parser = Parser::new();
         let text = "this\nis\na\ntest";
         let token = CodeBlock::new(text.to_string());
-        let result = parser.line_parse(&LineToken::CodeBlock(token));
+        let result = parser.line_parse(&LineToken::CodeBlock(token)).unwrap();
         assert_eq!("<pre><code>\nthis\nis\na\ntest\n</code></pre>\n", result);
     }
 
+    #[test]
+    fn test_undefined_reference_is_literal_text() {
+        let mut parser = Parser::new();
+        let result = parser.parse("see [text][missing] and ![alt][gone]");
+        assert_eq!(result, "<p>\nsee [text][missing] and ![alt][gone]\n</p>\n");
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_collect_undefined_references() {
+        let mut parser = Parser::new();
+        parser.set_collect_diagnostics(true);
+        parser.parse("[a][one]\n\n> - *![b][two]*\n\n[c][ok]\n\n[ok]:http://a.com");
+        assert_eq!(
+            parser.diagnostics(),
+            &[
+                Diagnostic::UndefinedReference(String::from("one")),
+                Diagnostic::UndefinedReference(String::from("two")),
+            ]
+        );
+        parser.parse("no links");
+        assert!(parser.diagnostics().is_empty());
+    }
+
+    #[test]
+    fn test_unknown_symbol_is_an_error() {
+        let parser = Parser::new();
+        let token = InlineToken::SpecialToken(SpecialToken::new('~', vec![]));
+        assert_eq!(parser.inline_parse(&token), Err(Error::UnknownSymbol('~')));
+        let token = InlineToken::DoubleSpecialToken(DoubleSpecialToken::new('`', vec![]));
+        let header = LineToken::HeaderToken(HeaderToken {
+            level: 1,
+            inline_tokens: vec![token],
+            span: Span::default(),
+        });
+        assert_eq!(parser.line_parse(&header), Err(Error::UnknownSymbol('`')));
+    }
+
+    #[test]
+    fn test_try_parse() {
+        let mut parser = Parser::new();
+        assert_eq!(
+            parser.try_parse("*a*"),
+            Ok(String::from("<p>\n<em>a</em>\n</p>\n"))
+        );
+    }
+
+    #[test]
+    fn test_escape_html() {
+        assert_eq!(
+            Parser::escape_html("a < b & c > \"d\""),
+            "a &lt; b &amp; c &gt; &quot;d&quot;"
+        );
+        assert_eq!(Parser::escape_html("café 'é'"), "café 'é'");
+    }
+
+    #[test]
+    fn test_code_block_is_escaped() {
+        let parser = Parser::new();
+        let token = CodeBlock::new(String::from("if a < b && c > d {}"));
+        let result = parser.line_parse(&LineToken::CodeBlock(token)).unwrap();
+        assert_eq!(
+            "<pre><code>\nif a &lt; b &amp;&amp; c &gt; d {}\n</code></pre>\n",
+            result
+        );
+    }
+
     #[test]
     fn test_image_token() {
         let parser = Parser::new();
@@ -342,9 +578,10 @@ mod test {
             link: String::from("link"),
             alt: String::from("alt"),
             need_note: false,
+            span: Span::default(),
         };
         let token = InlineToken::ImageToken(image_token);
-        let result = parser.inline_parse(&token);
+        let result = parser.inline_parse(&token).unwrap();
         assert_eq!("<img src=\"link\" alt=\"alt\">", result);
     }
 
@@ -355,9 +592,10 @@ mod test {
             link: String::from("link"),
             alt: String::from("alt"),
             need_note: false,
+            span: Span::default(),
         };
         let token = InlineToken::LinkToken(link_token);
-        let result = parser.inline_parse(&token);
+        let result = parser.inline_parse(&token).unwrap();
         assert_eq!("<a href=\"link\">alt</a>", result);
     }
 
@@ -366,10 +604,20 @@ mod test {
         let parser = Parser::new();
         let inline_tokens = vec![InlineToken::TextToken(TextToken {
             text: String::from("text token"),
+            span: Span::default(),
         })];
-        let quote_token = Quote { inline_tokens };
-        let result = parser.line_parse(&LineToken::Quote(quote_token));
-        assert_eq!(result, "<blockquote><p>\ntext token\n</p></blockquote>\n");
+        let quote_token = Quote {
+            children: vec![LineToken::Paragraph(Paragraph {
+                inline_tokens,
+                span: Span::default(),
+            })],
+            span: Span::default(),
+        };
+        let result = parser.line_parse(&LineToken::Quote(quote_token)).unwrap();
+        assert_eq!(
+            result,
+            "<blockquote>\n<p>\ntext token\n</p>\n</blockquote>\n"
+        );
     }
 
     #[test]
@@ -378,14 +626,21 @@ mod test {
         let inline_tokens = vec![
             InlineToken::TextToken(TextToken {
                 text: String::from("text token"),
+                span: Span::default(),
             }),
-            InlineToken::BreakToken,
+            InlineToken::BreakToken(Span::default()),
         ];
-        let quote_token = Quote { inline_tokens };
-        let result = parser.line_parse(&LineToken::Quote(quote_token));
+        let quote_token = Quote {
+            children: vec![LineToken::Paragraph(Paragraph {
+                inline_tokens,
+                span: Span::default(),
+            })],
+            span: Span::default(),
+        };
+        let result = parser.line_parse(&LineToken::Quote(quote_token)).unwrap();
         assert_eq!(
             result,
-            "<blockquote><p>\ntext token<br>\n</p></blockquote>\n"
+            "<blockquote>\n<p>\ntext token<br>\n</p>\n</blockquote>\n"
         );
     }
 
@@ -395,17 +650,25 @@ mod test {
         let inline_tokens = vec![
             InlineToken::TextToken(TextToken {
                 text: String::from("text token"),
+                span: Span::default(),
             }),
-            InlineToken::BreakToken,
+            InlineToken::BreakToken(Span::default()),
             InlineToken::TextToken(TextToken {
                 text: String::from("another token"),
+                span: Span::default(),
             }),
         ];
-        let quote_token = Quote { inline_tokens };
-        let result = parser.line_parse(&LineToken::Quote(quote_token));
+        let quote_token = Quote {
+            children: vec![LineToken::Paragraph(Paragraph {
+                inline_tokens,
+                span: Span::default(),
+            })],
+            span: Span::default(),
+        };
+        let result = parser.line_parse(&LineToken::Quote(quote_token)).unwrap();
         assert_eq!(
             result,
-            "<blockquote><p>\ntext token<br>another token\n</p></blockquote>\n"
+            "<blockquote>\n<p>\ntext token<br>another token\n</p>\n</blockquote>\n"
         );
     }
 
@@ -415,23 +678,33 @@ mod test {
         let inline_tokens = vec![
             InlineToken::TextToken(TextToken {
                 text: String::from("text token"),
+                span: Span::default(),
             }),
-            InlineToken::BreakToken,
+            InlineToken::BreakToken(Span::default()),
             InlineToken::TextToken(TextToken {
                 text: String::from("another token"),
+                span: Span::default(),
             }),
             InlineToken::SpecialToken(SpecialToken {
                 token: '*',
                 inline_tokens: vec![InlineToken::TextToken(TextToken {
                     text: String::from("another test"),
+                    span: Span::default(),
                 })],
+                span: Span::default(),
             }),
         ];
-        let quote_token = Quote { inline_tokens };
-        let result = parser.line_parse(&LineToken::Quote(quote_token));
+        let quote_token = Quote {
+            children: vec![LineToken::Paragraph(Paragraph {
+                inline_tokens,
+                span: Span::default(),
+            })],
+            span: Span::default(),
+        };
+        let result = parser.line_parse(&LineToken::Quote(quote_token)).unwrap();
         assert_eq!(
             result,
-            "<blockquote><p>\ntext token<br>another token<em>another test</em>\n</p></blockquote>\n"
+            "<blockquote>\n<p>\ntext token<br>another token<em>another test</em>\n</p>\n</blockquote>\n"
         );
     }
 
@@ -439,30 +712,31 @@ mod test {
     fn test_ordered_list() {
         let parser = Parser::new();
         let token = OrderedListBlock {
-            start: '1',
+            start: 1,
             symbol: ')',
+            tight: true,
             lists: vec![
                 LineToken::OrderedList(OrderedList {
-                    order: '1',
+                    order: 1,
                     symbol: ')',
-                    inline_tokens: vec![InlineToken::TextToken(TextToken {
-                        text: String::from("first"),
-                    })],
+                    checked: None,
+                    children: vec![paragraph_factory("first")],
+                    span: Span::default(),
                 }),
                 LineToken::OrderedList(OrderedList {
-                    order: '1',
+                    order: 1,
                     symbol: ')',
-                    inline_tokens: vec![InlineToken::TextToken(TextToken {
-                        text: String::from("second"),
-                    })],
+                    checked: None,
+                    children: vec![paragraph_factory("second")],
+                    span: Span::default(),
                 }),
             ],
+            span: Span::default(),
         };
-        let result = parser.line_parse(&LineToken::OrderedListBlock(token));
-        assert_eq!(
-            result,
-            "<ol start=\"1\">\n<li>first</li>\n<li>second</li>\n</ol>\n"
-        );
+        let result = parser
+            .line_parse(&LineToken::OrderedListBlock(token))
+            .unwrap();
+        assert_eq!(result, "<ol>\n<li>first</li>\n<li>second</li>\n</ol>\n");
     }
 
     #[test]
@@ -470,28 +744,32 @@ mod test {
         let parser = Parser::new();
         let token = UnorderedListBlock {
             symbol: '*',
+            tight: true,
             lists: vec![
                 LineToken::UnorderedList(UnorderedList {
                     symbol: '*',
-                    inline_tokens: vec![InlineToken::TextToken(TextToken {
-                        text: String::from("first"),
-                    })],
+                    checked: None,
+                    children: vec![paragraph_factory("first")],
+                    span: Span::default(),
                 }),
                 LineToken::UnorderedList(UnorderedList {
                     symbol: '*',
-                    inline_tokens: vec![InlineToken::TextToken(TextToken {
-                        text: String::from("second"),
-                    })],
+                    checked: None,
+                    children: vec![paragraph_factory("second")],
+                    span: Span::default(),
                 }),
             ],
+            span: Span::default(),
         };
-        let result = parser.line_parse(&LineToken::UnorderedListBlock(token));
+        let result = parser
+            .line_parse(&LineToken::UnorderedListBlock(token))
+            .unwrap();
         assert_eq!(result, "<ul>\n<li>first</li>\n<li>second</li>\n</ul>\n");
     }
 
     #[test]
     fn test_link_with_need_note() {
-        let text = "[alt][link]\n[link]:http://a.com";
+        let text = "[alt][link]\n\n[link]:http://a.com";
         let mut parser = Parser::new();
         let result = parser.parse(text);
         assert_eq!(parser.notes.len(), 1);
@@ -501,7 +779,7 @@ mod test {
 
     #[test]
     fn test_image_with_need_note() {
-        let text = "![alt][link]\n[link]:http://a.com";
+        let text = "![alt][link]\n\n[link]:http://a.com";
         let mut parser = Parser::new();
         let result = parser.parse(text);
         assert_eq!(parser.notes.len(), 1);
@@ -512,11 +790,196 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_soft_and_hard_breaks() {
+        let mut parser = Parser::new();
+        let result = parser.parse("foo\nbar  \nbaz\\\nqux");
+        assert_eq!(result, "<p>\nfoo\nbar<br>baz<br>qux\n</p>\n");
+    }
+
     #[test]
     fn test_horizontal_rule() {
-        let token = LineToken::HorizontalRule;
+        let token = LineToken::HorizontalRule(Span::default());
         let parser = Parser::new();
-        let result = parser.line_parse(&token);
+        let result = parser.line_parse(&token).unwrap();
         assert_eq!("<hr>\n", result);
     }
+
+    #[test]
+    fn test_quote_with_nested_list() {
+        let mut parser = Parser::new();
+        let result = parser.parse("> - first\n> - second");
+        assert_eq!(
+            result,
+            "<blockquote>\n<ul>\n<li>first</li>\n<li>second</li>\n</ul>\n</blockquote>\n"
+        );
+    }
+
+    #[test]
+    fn test_loose_list_item_with_code_block() {
+        let mut parser = Parser::new();
+        let result = parser.parse("- first\n\n  ```\n  code\n  ```");
+        assert_eq!(
+            result,
+            "<ul>\n<li>\n<p>\nfirst\n</p>\n<pre><code>\ncode\n</code></pre>\n</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn test_tight_list_item_with_nested_quote() {
+        let mut parser = Parser::new();
+        let result = parser.parse("- item\n  > quoted");
+        assert_eq!(
+            result,
+            "<ul>\n<li>item\n<blockquote>\n<p>\nquoted\n</p>\n</blockquote>\n</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn test_nested_list_by_indentation() {
+        let mut parser = Parser::new();
+        let result = parser.parse("- one\n  1. two\n     - three\n- four");
+        assert_eq!(
+            result,
+            "<ul>\n<li>one\n<ol>\n<li>two\n<ul>\n<li>three</li>\n</ul>\n</li>\n</ol>\n</li>\n<li>four</li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn test_ordered_list_start_attribute() {
+        let parser = Parser::new();
+        let tokens = Tokenizer::tokenizer("10. ten\n11. eleven");
+        let result = parser.line_parse(&tokens[0]).unwrap();
+        assert_eq!(
+            result,
+            "<ol start=\"10\">\n<li>ten</li>\n<li>eleven</li>\n</ol>\n"
+        );
+        let tokens = Tokenizer::tokenizer("0. zero");
+        let result = parser.line_parse(&tokens[0]).unwrap();
+        assert_eq!(result, "<ol start=\"0\">\n<li>zero</li>\n</ol>\n");
+    }
+
+    #[test]
+    fn test_code_block_with_language() {
+        let mut parser = Parser::new();
+        let result = parser.parse("~~~~ rust\nlet a = 1;\n~~~~");
+        assert_eq!(
+            result,
+            "<pre><code class=\"language-rust\">\nlet a = 1;\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_indented_code_block() {
+        let mut parser = Parser::new();
+        let result = parser.parse("    a *b* _c_");
+        assert_eq!(result, "<pre><code>\na *b* _c_\n</code></pre>\n");
+    }
+
+    #[test]
+    fn test_source_positions() {
+        let mut parser = Parser::new();
+        parser.set_source_positions(true);
+        let result = parser.parse("# a\n\n> b\n\n- c\n  d\n\n---");
+        assert_eq!(
+            result,
+            "<h1 data-sourcepos=\"1:1-1:3\">a</h1>\n\
+             <blockquote data-sourcepos=\"3:1-3:3\">\n<p data-sourcepos=\"3:3-3:3\">\nb\n</p>\n</blockquote>\n\
+             <ul data-sourcepos=\"5:1-6:3\">\n<li data-sourcepos=\"5:1-6:3\">c\nd</li>\n</ul>\n\
+             <hr data-sourcepos=\"8:1-8:3\">\n"
+        );
+        let result = parser.parse("1. a\n\n```\nb\n```");
+        assert_eq!(
+            result,
+            "<ol data-sourcepos=\"1:1-1:4\">\n<li data-sourcepos=\"1:1-1:4\">a</li>\n</ol>\n\
+             <pre data-sourcepos=\"3:1-5:3\"><code>\nb\n</code></pre>\n"
+        );
+        parser.set_source_positions(false);
+        assert_eq!(parser.parse("a"), "<p>\na\n</p>\n");
+    }
+
+    struct Figures;
+
+    impl Renderer for Figures {
+        fn image(&mut self, output: &mut Output, image: &crate::node::Image) -> fmt::Result {
+            fmt::Write::write_str(output, "<figure>")?;
+            html::image(self, output, image)?;
+            fmt::Write::write_str(output, "</figure>")
+        }
+    }
+
+    #[test]
+    fn test_parse_with_renderer() {
+        let mut parser = Parser::new();
+        let result = parser.parse_with("![a][b]\n\n[b]: c.png", &mut Figures);
+        assert_eq!(
+            result,
+            "<p>\n<figure><img src=\" c.png\" alt=\"a\"></figure>\n</p>\n\n"
+        );
+    }
+
+    #[test]
+    fn test_parse_with_renderer_in_safe_mode() {
+        let mut parser = Parser::with_options(ParserOptions {
+            safe: true,
+            ..ParserOptions::default()
+        });
+        let result = parser.parse_with("[a](javascript:x) ![b](javascript:y) [c](d)", &mut Figures);
+        assert_eq!(
+            result,
+            "<p>\n<a href=\"\">a</a> <figure><img src=\"\" alt=\"b\"></figure> <a href=\"d\">c</a>\n</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_to_writers() {
+        let text = "# a\n\n- *b*\n- [c][d]\n\n[d]: e\n\n```\n<f>\n```";
+        let expected = Parser::new().parse(text);
+        let mut bytes = Vec::new();
+        Parser::new().render_to(text, &mut bytes).unwrap();
+        assert_eq!(String::from_utf8(bytes).unwrap(), expected);
+        let mut string = String::new();
+        Parser::new().render_fmt(text, &mut string).unwrap();
+        assert_eq!(string, expected);
+    }
+
+    /// Takes `capacity` bytes, then fails.
+    struct Full {
+        capacity: usize,
+    }
+
+    impl io::Write for Full {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if buf.len() > self.capacity {
+                return Err(io::Error::new(io::ErrorKind::WriteZero, "full"));
+            }
+            self.capacity -= buf.len();
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl fmt::Write for Full {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            io::Write::write(self, s.as_bytes())
+                .map(|_| ())
+                .map_err(|_| fmt::Error)
+        }
+    }
+
+    #[test]
+    fn test_render_to_returns_write_errors() {
+        let text = "a\n".repeat(10_000);
+        let error = Parser::new()
+            .render_to(&text, Full { capacity: 100 })
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+        let error = Parser::new()
+            .render_fmt(&text, &mut Full { capacity: 100 })
+            .unwrap_err();
+        assert_eq!(error, Error::Fmt(fmt::Error));
+    }
 }
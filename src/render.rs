@@ -0,0 +1,309 @@
+//! Rendering the node tree through a `Renderer`.
+//!
+//! A `Renderer` has one method for every kind of node. By default each method
+//! writes its node as HTML with the function of the same name in `html`, which
+//! renders the children back through the renderer. Override the methods of the
+//! nodes you want written differently, calling the `html` function from them to
+//! keep the default output around your own, or override all of them to write
+//! something other than HTML.
+use super::html;
+use super::node::{
+    Block, BlockQuote, Code, CodeBlock, Document, Emphasis, Heading, Image, Inline, Link,
+    LinkDefinition, List, ListItem, Paragraph, Strikethrough, Strong, Table, TableCell, Text,
+};
+use super::options::ParserOptions;
+use super::tokenizer::{Alignment, Span};
+
+use std::fmt;
+
+/// Where a `Renderer` writes to: any `fmt::Write`, as it goes. It remembers the
+/// last character written, so that block elements can start on a new line.
+pub struct Output<'a> {
+    sink: &'a mut dyn fmt::Write,
+    last: Option<char>,
+}
+
+impl<'a> Output<'a> {
+    pub fn new(sink: &'a mut dyn fmt::Write) -> Self {
+        Output::after(sink, None)
+    }
+
+    /// Writes to `sink`, which already ends with `last`.
+    pub(crate) fn after(sink: &'a mut dyn fmt::Write, last: Option<char>) -> Self {
+        Output { sink, last }
+    }
+
+    /// Whether nothing was written yet, or the last thing written ended a
+    /// line.
+    pub fn at_line_start(&self) -> bool {
+        matches!(self.last, None | Some('\n'))
+    }
+}
+
+impl<'a> fmt::Write for Output<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if let Some(c) = s.chars().next_back() {
+            self.last = Some(c);
+        }
+        self.sink.write_str(s)
+    }
+}
+
+/// Writes the nodes of a document to an `Output`.
+///
+/// Every method returns the first error of the output, and stops writing
+/// there. Reference links and images are written as they are, so call
+/// `Document::resolve_references` first unless the renderer looks up the
+/// labels itself.
+pub trait Renderer {
+    /// Renders `document` to a new string.
+    fn render(&mut self, document: &Document) -> String {
+        let mut output = String::new();
+        // A `String` never fails to write, so an error can only come from the
+        // renderer itself, and leaves what was written before it.
+        let _ = self.render_fmt(&mut output, document);
+        output
+    }
+
+    /// Renders `document` to `sink` as it goes.
+    fn render_fmt(&mut self, sink: &mut dyn fmt::Write, document: &Document) -> fmt::Result {
+        self.document(&mut Output::new(sink), document)
+    }
+
+    /// The options that the default methods write HTML with.
+    fn options(&self) -> ParserOptions {
+        ParserOptions::default()
+    }
+
+    fn document(&mut self, output: &mut Output, document: &Document) -> fmt::Result {
+        render_blocks(self, output, &document.children, false)
+    }
+
+    fn heading(&mut self, output: &mut Output, heading: &Heading) -> fmt::Result {
+        html::heading(self, output, heading)
+    }
+
+    /// A paragraph. `tight` is set in the items of a tight list.
+    fn paragraph(
+        &mut self,
+        output: &mut Output,
+        paragraph: &Paragraph,
+        tight: bool,
+    ) -> fmt::Result {
+        html::paragraph(self, output, paragraph, tight)
+    }
+
+    fn code_block(&mut self, output: &mut Output, code_block: &CodeBlock) -> fmt::Result {
+        html::code_block(self, output, code_block)
+    }
+
+    fn block_quote(&mut self, output: &mut Output, block_quote: &BlockQuote) -> fmt::Result {
+        html::block_quote(self, output, block_quote)
+    }
+
+    fn list(&mut self, output: &mut Output, list: &List) -> fmt::Result {
+        html::list(self, output, list)
+    }
+
+    /// An item of a list, and whether the list is tight.
+    fn list_item(&mut self, output: &mut Output, item: &ListItem, tight: bool) -> fmt::Result {
+        html::list_item(self, output, item, tight)
+    }
+
+    fn link_definition(&mut self, output: &mut Output, definition: &LinkDefinition) -> fmt::Result {
+        html::link_definition(self, output, definition)
+    }
+
+    fn thematic_break(&mut self, output: &mut Output, span: Span) -> fmt::Result {
+        html::thematic_break(self, output, span)
+    }
+
+    fn table(&mut self, output: &mut Output, table: &Table) -> fmt::Result {
+        html::table(self, output, table)
+    }
+
+    /// A cell of a table, the alignment of its column, and whether it is in
+    /// the header row.
+    fn table_cell(
+        &mut self,
+        output: &mut Output,
+        cell: &TableCell,
+        alignment: Alignment,
+        head: bool,
+    ) -> fmt::Result {
+        html::table_cell(self, output, cell, alignment, head)
+    }
+
+    fn text(&mut self, output: &mut Output, text: &Text) -> fmt::Result {
+        html::text(self, output, text)
+    }
+
+    fn emphasis(&mut self, output: &mut Output, emphasis: &Emphasis) -> fmt::Result {
+        html::emphasis(self, output, emphasis)
+    }
+
+    fn strong(&mut self, output: &mut Output, strong: &Strong) -> fmt::Result {
+        html::strong(self, output, strong)
+    }
+
+    fn strikethrough(&mut self, output: &mut Output, strikethrough: &Strikethrough) -> fmt::Result {
+        html::strikethrough(self, output, strikethrough)
+    }
+
+    fn code(&mut self, output: &mut Output, code: &Code) -> fmt::Result {
+        html::code(self, output, code)
+    }
+
+    fn link(&mut self, output: &mut Output, link: &Link) -> fmt::Result {
+        html::link(self, output, link)
+    }
+
+    fn image(&mut self, output: &mut Output, image: &Image) -> fmt::Result {
+        html::image(self, output, image)
+    }
+
+    fn hard_break(&mut self, output: &mut Output, span: Span) -> fmt::Result {
+        html::hard_break(self, output, span)
+    }
+
+    fn soft_break(&mut self, output: &mut Output, span: Span) -> fmt::Result {
+        html::soft_break(self, output, span)
+    }
+}
+
+/// Renders `blocks`, which are in the items of a tight list if `tight` is set.
+pub fn render_blocks<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    blocks: &[Block],
+    tight: bool,
+) -> fmt::Result {
+    for block in blocks {
+        render_block(renderer, output, block, tight)?;
+    }
+    Ok(())
+}
+
+/// Calls the method of `renderer` for the kind of `block`.
+pub fn render_block<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    block: &Block,
+    tight: bool,
+) -> fmt::Result {
+    match block {
+        Block::Heading(b) => renderer.heading(output, b),
+        Block::Paragraph(b) => renderer.paragraph(output, b, tight),
+        Block::CodeBlock(b) => renderer.code_block(output, b),
+        Block::BlockQuote(b) => renderer.block_quote(output, b),
+        Block::List(b) => renderer.list(output, b),
+        Block::LinkDefinition(b) => renderer.link_definition(output, b),
+        Block::ThematicBreak(span) => renderer.thematic_break(output, *span),
+        Block::Table(b) => renderer.table(output, b),
+    }
+}
+
+pub fn render_inlines<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    inlines: &[Inline],
+) -> fmt::Result {
+    for inline in inlines {
+        render_inline(renderer, output, inline)?;
+    }
+    Ok(())
+}
+
+/// Calls the method of `renderer` for the kind of `inline`.
+pub fn render_inline<R: Renderer + ?Sized>(
+    renderer: &mut R,
+    output: &mut Output,
+    inline: &Inline,
+) -> fmt::Result {
+    match inline {
+        Inline::Text(i) => renderer.text(output, i),
+        Inline::Emphasis(i) => renderer.emphasis(output, i),
+        Inline::Strong(i) => renderer.strong(output, i),
+        Inline::Strikethrough(i) => renderer.strikethrough(output, i),
+        Inline::Code(i) => renderer.code(output, i),
+        Inline::Link(i) => renderer.link(output, i),
+        Inline::Image(i) => renderer.image(output, i),
+        Inline::HardBreak(span) => renderer.hard_break(output, *span),
+        Inline::SoftBreak(span) => renderer.soft_break(output, *span),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Write;
+
+    /// Wraps images in `<figure>`, keeping the rest of the HTML.
+    struct Figures;
+
+    impl Renderer for Figures {
+        fn image(&mut self, output: &mut Output, image: &Image) -> fmt::Result {
+            output.write_str("<figure>")?;
+            html::image(self, output, image)?;
+            output.write_str("</figure>")
+        }
+    }
+
+    #[test]
+    fn test_override_one_element() {
+        let document = Document::parse("> *![a](b.png)*");
+        assert_eq!(
+            Figures.render(&document),
+            "<blockquote>\n<p>\n<em><figure><img src=\"b.png\" alt=\"a\"></figure></em>\n</p>\n</blockquote>\n"
+        );
+    }
+
+    /// Writes the text of a document without any markup.
+    struct PlainText;
+
+    impl Renderer for PlainText {
+        fn heading(&mut self, output: &mut Output, heading: &Heading) -> fmt::Result {
+            render_inlines(self, output, &heading.children)?;
+            output.write_char('\n')
+        }
+
+        fn paragraph(
+            &mut self,
+            output: &mut Output,
+            paragraph: &Paragraph,
+            _tight: bool,
+        ) -> fmt::Result {
+            render_inlines(self, output, &paragraph.children)?;
+            output.write_char('\n')
+        }
+
+        fn list_item(&mut self, output: &mut Output, item: &ListItem, tight: bool) -> fmt::Result {
+            render_blocks(self, output, &item.children, tight)
+        }
+
+        fn list(&mut self, output: &mut Output, list: &List) -> fmt::Result {
+            for item in &list.items {
+                self.list_item(output, item, list.tight)?;
+            }
+            Ok(())
+        }
+
+        fn text(&mut self, output: &mut Output, text: &Text) -> fmt::Result {
+            output.write_str(&text.text)
+        }
+
+        fn emphasis(&mut self, output: &mut Output, emphasis: &Emphasis) -> fmt::Result {
+            render_inlines(self, output, &emphasis.children)
+        }
+
+        fn link(&mut self, output: &mut Output, link: &Link) -> fmt::Result {
+            output.write_str(&link.text)
+        }
+    }
+
+    #[test]
+    fn test_new_backend() {
+        let document = Document::parse("# a & b\n\n- [c](d) *e*");
+        assert_eq!(PlainText.render(&document), "a & b\nc e\n");
+    }
+}
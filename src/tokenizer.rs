@@ -1,57 +1,125 @@
 extern crate regex;
 
+use super::options::Extensions;
+use super::Error;
+use span::LineStarts;
+
+/// Returns the `Regex` for a pattern, compiled the first time the expression
+/// is evaluated and reused after that.
+macro_rules! regex {
+    ($pattern:expr) => {{
+        static RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+        RE.get_or_init(|| regex::Regex::new($pattern).unwrap())
+    }};
+}
+
 pub mod inline_token;
 pub mod line_token;
+pub mod span;
 
 pub use inline_token::{
-    DoubleSpecialToken, ImageToken, InlineToken, LinkToken, SpecialToken, TextToken,
+    DoubleSpecialToken, ImageToken, InlineToken, LinkToken, SpecialToken, StrikethroughToken,
+    TextToken,
 };
 pub use line_token::{
-    CodeBlock, HeaderToken, LineToken, NoteToken, OrderedList, OrderedListBlock, Paragraph, Quote,
-    UnorderedList, UnorderedListBlock,
+    Alignment, CodeBlock, HeaderToken, LineToken, NoteToken, OrderedList, OrderedListBlock,
+    Paragraph, Quote, Table, TableCell, TableRow, UnorderedList, UnorderedListBlock,
 };
+pub use span::{Position, Span};
+
+/// How deep quotes, lists and emphasis can be nested. Markers that would nest
+/// deeper are read as text, so that no input can overflow the stack.
+pub const MAX_DEPTH: usize = 100;
 
 pub struct Tokenizer {}
 
 impl Tokenizer {
+    /// Tokenizes `text`. Never panics; see `try_tokenize` for the errors
+    /// behind that.
     pub fn tokenizer(text: &str) -> Vec<LineToken> {
-        let mut result: Vec<LineToken> = Vec::new();
+        Tokenizer::try_tokenize(text).unwrap_or_default()
+    }
+
+    pub fn try_tokenize(text: &str) -> Result<Vec<LineToken>, Error> {
+        Tokenizer::try_tokenize_with(text, Extensions::default())
+    }
+
+    /// Tokenizes `text` with the syntax of `extensions` on top of CommonMark.
+    pub fn try_tokenize_with(text: &str, extensions: Extensions) -> Result<Vec<LineToken>, Error> {
         let lines = text.split("\n");
         let lines: Vec<&str> = lines.collect();
+        Tokenizer::tokenize_lines(&lines, 0, extensions)
+    }
+
+    /// Tokenizes text that has already been split into lines. Container blocks
+    /// call this again on their own lines, with their markers stripped, to
+    /// build their children. `depth` is the number of containers around the
+    /// lines; at `MAX_DEPTH` no more containers are started. Spans are
+    /// positions in the lines joined with newlines.
+    pub fn tokenize_lines(
+        lines: &[&str],
+        depth: usize,
+        extensions: Extensions,
+    ) -> Result<Vec<LineToken>, Error> {
+        let mut result: Vec<LineToken> = Vec::new();
         let mut i: usize = 0;
         while i < lines.len() {
             let line = lines[i];
-            if line == "" {
+            if LineToken::is_blank(line) {
                 i += 1;
                 continue;
             }
-            if line == "```" {
-                let (token, temp) = CodeBlock::tokenizer(&lines, i);
-                i = temp;
+            if let Some((token, index)) = CodeBlock::indented_tokenizer(lines, i) {
+                i = index;
                 result.push(token);
-            } else if line[0..1] == *">" {
-                let (token, index) = Quote::tokenizer(&lines, i);
+            } else if let Some((token, index)) = CodeBlock::tokenizer(lines, i) {
+                i = index;
+                result.push(token);
+            } else if LineToken::is_horizontal_rule(line) {
+                let span = Span::from_lines(i + 1, 1, i + 1, line.len() + 1);
+                result.push(LineToken::HorizontalRule(span))
+            } else if depth < MAX_DEPTH && line.starts_with('>') {
+                let (token, index) = Quote::tokenizer(lines, i, depth, extensions)?;
                 i = index;
                 result.push(token);
-            } else if let Some(token) = LineToken::is_list(line) {
+            } else if let Some((token, index)) =
+                LineToken::list_tokenizer(lines, i, depth, extensions)?
+            {
+                let blank_inside = lines[i..index].iter().any(|l| LineToken::is_blank(l));
                 if LineToken::same_list_block_as_prev(&token, &result) {
-                    LineToken::push_to_last_list_block(&mut result, token);
+                    if LineToken::is_blank(lines[i - 1]) || blank_inside {
+                        LineToken::loosen_last_list_block(&mut result);
+                    }
+                    LineToken::push_to_last_list_block(&mut result, token)?;
                 } else {
-                    let token = LineToken::new_list_block(token);
-                    result.push(token);
+                    result.push(LineToken::new_list_block(token)?);
+                    if blank_inside {
+                        LineToken::loosen_last_list_block(&mut result);
+                    }
                 }
-            } else if let Some(token) = HeaderToken::try_tokenize(line) {
+                i = index;
+            } else if let Some(mut token) = HeaderToken::try_tokenize(line, extensions)
+                .or_else(|| NoteToken::try_tokenize(line))
+            {
+                token.map_spans(&|p| Position {
+                    line: p.line + i,
+                    ..p
+                });
                 result.push(token);
-            } else if let Some(token) = NoteToken::try_tokenize(line) {
+            } else if let Some((token, index)) = Table::tokenizer(lines, i, extensions) {
+                i = index;
                 result.push(token);
-            } else if LineToken::is_horizontal_rule(line) {
-                result.push(LineToken::HorizontalRule)
             } else {
-                let token = Paragraph::tokenizer(line);
+                let (token, index) = Paragraph::tokenizer(lines, i, extensions);
+                i = index;
                 result.push(token);
             }
             i += 1;
         }
-        result
+        let starts = LineStarts::from_lines(lines);
+        for token in &mut result {
+            token.map_spans(&|p| starts.offset(p));
+        }
+        Ok(result)
     }
 }
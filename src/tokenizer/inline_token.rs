@@ -1,122 +1,473 @@
 extern crate regex;
-use regex::Regex;
+use super::span::{LineStarts, Position, Span};
+use super::{Extensions, MAX_DEPTH};
+use std::collections::HashMap;
 
-const SPECIAL_TOKEN: &'static [char] = &['_', '*', '`', '[', '!'];
+const SPECIAL_TOKEN: &[u8] = b"_*`[!<";
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InlineToken {
     TextToken(TextToken),
     SpecialToken(SpecialToken),
     DoubleSpecialToken(DoubleSpecialToken),
     LinkToken(LinkToken),
     ImageToken(ImageToken),
-    BreakToken,
+    StrikethroughToken(StrikethroughToken),
+    BreakToken(Span),
+    SoftBreak(Span),
 }
 
 impl InlineToken {
+    pub fn is_special(byte: u8) -> bool {
+        SPECIAL_TOKEN.contains(&byte)
+    }
+
+    /// Whether `byte` is special with the syntax of `extensions`: `~` is too
+    /// if it has strikethrough.
+    pub fn is_special_with(byte: u8, extensions: Extensions) -> bool {
+        InlineToken::is_special(byte)
+            || (cfg!(feature = "strikethrough") && extensions.strikethrough && byte == b'~')
+    }
+
     pub fn is_prev_backslash(text: &str, index: usize) -> bool {
-        if index == 0 {
-            false
-        } else {
-            text[index - 1..index] == *"\\"
-        }
+        index > 0 && text.as_bytes()[index - 1] == b'\\'
     }
 
-    pub fn try_special_token(text: &str, first_token: &char) -> (Option<InlineToken>, usize) {
-        // TODO: Clean this mess
-        let re_symbol: &str;
-        let temp = &[*first_token as u8];
-        let borrow = std::str::from_utf8(temp).unwrap();
-        let symbol = borrow.chars().next().unwrap();
-        if symbol == '*' {
-            re_symbol = r"\*";
-        } else {
-            re_symbol = borrow;
+    /// Turns the newline at offset `newline`, after `tokens`, into a hard
+    /// break if the text before it ends with two or more spaces or a
+    /// backslash, and into a soft break otherwise. Spaces at the end of the
+    /// line are dropped either way, and become part of the break's span.
+    pub fn line_break(tokens: &mut Vec<InlineToken>, newline: usize) -> InlineToken {
+        let mut hard = false;
+        let mut start = newline;
+        if let Some(InlineToken::TextToken(t)) = tokens.last_mut() {
+            let len = t.text.trim_end_matches(' ').len();
+            hard = t.text.len() - len >= 2;
+            t.text.truncate(len);
+            if !hard && t.text.ends_with('\\') {
+                t.text.pop();
+                hard = true;
+            }
+            start = t.span.end.offset.min(t.span.start.offset + t.text.len());
+            t.span.end.offset = start;
+            if t.text.is_empty() {
+                tokens.pop();
+            }
         }
-        if let (Some(t), i) = DoubleSpecialToken::try_tokenize(text, symbol, re_symbol) {
-            (Some(t), i)
-        } else if let (Some(t), i) = SpecialToken::try_tokenize(text, symbol, re_symbol) {
-            (Some(t), i)
+        let span = Span::from_offsets(start, newline + 1);
+        if hard {
+            InlineToken::BreakToken(span)
         } else {
-            (None, 0)
+            InlineToken::SoftBreak(span)
         }
     }
 
     pub fn get_text_token(text: String) -> InlineToken {
-        InlineToken::TextToken(TextToken { text })
+        InlineToken::TextToken(TextToken::new(text, Span::default()))
+    }
+
+    /// The source text the token was built from.
+    pub fn span(&self) -> Span {
+        match self {
+            InlineToken::TextToken(t) => t.span,
+            InlineToken::SpecialToken(t) => t.span,
+            InlineToken::DoubleSpecialToken(t) => t.span,
+            InlineToken::LinkToken(t) => t.span,
+            InlineToken::ImageToken(t) => t.span,
+            InlineToken::StrikethroughToken(t) => t.span,
+            InlineToken::BreakToken(span) | InlineToken::SoftBreak(span) => *span,
+        }
+    }
+
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            InlineToken::TextToken(t) => &mut t.span,
+            InlineToken::SpecialToken(t) => &mut t.span,
+            InlineToken::DoubleSpecialToken(t) => &mut t.span,
+            InlineToken::LinkToken(t) => &mut t.span,
+            InlineToken::ImageToken(t) => &mut t.span,
+            InlineToken::StrikethroughToken(t) => &mut t.span,
+            InlineToken::BreakToken(span) | InlineToken::SoftBreak(span) => span,
+        }
+    }
+
+    /// Applies `f` to both ends of the span of this token and of every token
+    /// inside it.
+    pub fn map_spans(&mut self, f: &dyn Fn(Position) -> Position) {
+        self.span_mut().map(f);
+        match self {
+            InlineToken::SpecialToken(t) => t.inline_tokens.iter_mut().for_each(|t| t.map_spans(f)),
+            InlineToken::DoubleSpecialToken(t) => {
+                t.inline_tokens.iter_mut().for_each(|t| t.map_spans(f))
+            }
+            InlineToken::StrikethroughToken(t) => {
+                t.inline_tokens.iter_mut().for_each(|t| t.map_spans(f))
+            }
+            _ => (),
+        }
     }
 
     pub fn get_nth_cap(mat: &regex::Captures, n: usize) -> String {
         String::from(mat.get(n).unwrap().as_str())
     }
 
-    pub fn get_alt_and_link(mat: &regex::Captures) -> (String, String) {
-        let alt = InlineToken::get_nth_cap(&mat, 1);
-        let link = InlineToken::get_nth_cap(&mat, 2);
-        (alt, link)
+    /// Splits `inline_text` into inline tokens. Their spans are positions in
+    /// `inline_text`.
+    pub fn tokenizer(inline_text: &str) -> Vec<InlineToken> {
+        InlineToken::tokenizer_with(inline_text, Extensions::default())
     }
 
-    pub fn tokenizer(inline_text: &str) -> Vec<InlineToken> {
+    /// Splits `inline_text` into inline tokens, with the syntax of
+    /// `extensions` on top of CommonMark.
+    ///
+    /// `i` is always a byte offset into `inline_text`. Every special token is
+    /// ASCII, and ASCII bytes never occur inside a multibyte UTF-8 sequence, so
+    /// scanning bytes for them only ever stops on a char boundary.
+    pub fn tokenizer_with(inline_text: &str, extensions: Extensions) -> Vec<InlineToken> {
         let mut tokens: Vec<InlineToken> = Vec::new();
-        let n = inline_text.len();
-        let chars: Vec<char> = inline_text.chars().collect();
-        let special_tokens = SPECIAL_TOKEN;
+        let mut delimiters: Vec<Delimiter> = Vec::new();
+        let mut links = LinkScanner::new(inline_text);
+        let bytes = inline_text.as_bytes();
+        let n = bytes.len();
         let mut i: usize = 0;
         while i < n {
-            let token: InlineToken;
-            if special_tokens.contains(&chars[i]) && !InlineToken::is_prev_backslash(inline_text, i)
+            let mut token: InlineToken;
+            let start = i;
+            let c = bytes[i] as char;
+            if c == '\n' {
+                token = InlineToken::line_break(&mut tokens, i);
+                tokens.push(token);
+                i += 1;
+                continue;
+            } else if let Some((t, length)) =
+                LinkToken::try_tokenize_extended(inline_text, i, extensions)
+            {
+                token = InlineToken::LinkToken(t);
+                i += length;
+            } else if InlineToken::is_special_with(bytes[i], extensions)
+                && !InlineToken::is_prev_backslash(inline_text, i)
             {
                 let left_text = &inline_text[i..];
-                if chars[i] == '[' {
-                    if let Some(t) = LinkToken::try_tokenize(left_text) {
-                        i = i + t.len();
+                if c == '[' {
+                    if let Some(t) = LinkToken::try_tokenize_at(&mut links, i) {
+                        i += t.len();
                         token = InlineToken::LinkToken(t);
                     } else {
-                        token = InlineToken::get_text_token(chars[i].to_string());
+                        token = InlineToken::get_text_token(c.to_string());
                         i += 1;
                     }
-                } else if chars[i] == '!' {
-                    if let Some(t) = ImageToken::try_tokenize(left_text) {
-                        i = i + t.len();
+                } else if c == '!' {
+                    if let Some(t) = ImageToken::try_tokenize_at(&mut links, i) {
+                        i += t.len();
                         token = InlineToken::ImageToken(t);
                     } else {
-                        token = InlineToken::get_text_token(chars[i].to_string());
+                        token = InlineToken::get_text_token(c.to_string());
                         i += 1;
                     }
-                } else {
-                    let (option, step) = InlineToken::try_special_token(left_text, &chars[i]);
+                } else if c == '<' {
+                    if let Some((t, length)) = LinkToken::try_tokenize_autolink(left_text) {
+                        token = InlineToken::LinkToken(t);
+                        i += length;
+                    } else {
+                        token = InlineToken::get_text_token(c.to_string());
+                        i += 1;
+                    }
+                } else if c == '`' {
+                    let (option, step) = SpecialToken::try_tokenize_code(left_text);
                     if let Some(t) = option {
-                        i += step;
                         token = t;
                     } else {
-                        token = InlineToken::get_text_token(chars[i].to_string());
-                        i += 1;
+                        token = InlineToken::get_text_token(left_text[..step].to_string());
                     }
+                    i += step;
+                } else {
+                    let delimiter = Delimiter::scan(inline_text, i, tokens.len());
+                    token = InlineToken::get_text_token(left_text[..delimiter.length].to_string());
+                    i += delimiter.length;
+                    delimiters.push(delimiter);
                 }
             } else {
                 let mut temp = i + 1;
-                while temp < n && !special_tokens.contains(&chars[temp]) {
+                while temp < n
+                    && !InlineToken::is_special_with(bytes[temp], extensions)
+                    && bytes[temp] != b'\n'
+                    && LinkToken::try_tokenize_extended(inline_text, temp, extensions).is_none()
+                {
                     temp += 1;
                 }
-                token = InlineToken::TextToken(TextToken {
-                    text: inline_text[i..temp].to_string(),
-                });
+                token = InlineToken::get_text_token(inline_text[i..temp].to_string());
                 i = temp;
             }
+            // Tokens built from `left_text` have spans relative to it.
+            token.map_spans(&|p| Position {
+                offset: p.offset + start,
+                ..p
+            });
+            *token.span_mut() = Span::from_offsets(start, i);
             tokens.push(token);
         }
+        Delimiter::process_emphasis(&mut delimiters);
+        let mut tokens = Delimiter::build_emphasis(tokens, delimiters);
+        let starts = LineStarts::new(inline_text);
+        for token in &mut tokens {
+            token.map_spans(&|p| starts.position(p.offset));
+        }
         tokens
     }
 }
 
+/// A run of `*` or `_` characters that may open or close emphasis, or of `~`
+/// characters that may open or close strikethrough.
+///
+/// Runs are matched with the delimiter stack algorithm of the CommonMark spec.
+/// A `~` run only matches a run of the same length, one or two characters,
+/// and is used whole.
+/// Every run starts out as a text token; `index` is the position of that token.
+/// Each match takes one or two characters from both runs and records them in
+/// `opens` and `closes`, in the order the matches were made.
 #[derive(Debug)]
+struct Delimiter {
+    index: usize,
+    symbol: char,
+    length: usize,
+    count: usize,
+    can_open: bool,
+    can_close: bool,
+    active: bool,
+    opens: Vec<usize>,
+    closes: Vec<usize>,
+}
+
+impl Delimiter {
+    fn is_punctuation(c: char) -> bool {
+        c.is_ascii_punctuation() || (!c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace())
+    }
+
+    /// Scans the run of delimiters starting at byte `start` of `text`, which
+    /// becomes the token at `index`. The start and end of the text count as
+    /// whitespace when deciding whether the run is left or right flanking.
+    fn scan(text: &str, start: usize, index: usize) -> Delimiter {
+        let symbol = text.as_bytes()[start] as char;
+        let length = text[start..].len() - text[start..].trim_start_matches(symbol).len();
+        let before = text[..start].chars().next_back().unwrap_or(' ');
+        let after = text[start + length..].chars().next().unwrap_or(' ');
+        let left_flanking = !after.is_whitespace()
+            && (!Delimiter::is_punctuation(after)
+                || before.is_whitespace()
+                || Delimiter::is_punctuation(before));
+        let right_flanking = !before.is_whitespace()
+            && (!Delimiter::is_punctuation(before)
+                || after.is_whitespace()
+                || Delimiter::is_punctuation(after));
+        let (can_open, can_close) = if symbol == '~' && length > 2 {
+            (false, false)
+        } else if symbol == '_' {
+            (
+                left_flanking && (!right_flanking || Delimiter::is_punctuation(before)),
+                right_flanking && (!left_flanking || Delimiter::is_punctuation(after)),
+            )
+        } else {
+            (left_flanking, right_flanking)
+        };
+        Delimiter {
+            index,
+            symbol,
+            length,
+            count: length,
+            can_open,
+            can_close,
+            active: true,
+            opens: Vec::new(),
+            closes: Vec::new(),
+        }
+    }
+
+    /// Whether `opener` and `closer` can't match because of the "multiple of
+    /// 3" rule, which keeps `*foo**bar*` from closing at `**`.
+    fn is_multiple_of_three(opener: &Delimiter, closer: &Delimiter) -> bool {
+        (opener.can_close || closer.can_open)
+            && (opener.length + closer.length) % 3 == 0
+            && !(opener.length % 3 == 0 && closer.length % 3 == 0)
+    }
+
+    /// Matches closers with the nearest possible opener before them. Once a
+    /// pair matches, the delimiters between them can't be used anymore.
+    fn process_emphasis(delimiters: &mut [Delimiter]) {
+        let mut openers_bottom: HashMap<(char, bool, usize), usize> = HashMap::new();
+        for closer in 0..delimiters.len() {
+            while delimiters[closer].active
+                && delimiters[closer].can_close
+                && delimiters[closer].count > 0
+            {
+                let c = &delimiters[closer];
+                let key = (c.symbol, c.can_open, c.length % 3);
+                let bottom = openers_bottom.get(&key).copied().unwrap_or(0);
+                let opener = (bottom..closer).rev().find(|&j| {
+                    let o = &delimiters[j];
+                    o.active
+                        && o.can_open
+                        && o.count > 0
+                        && o.symbol == c.symbol
+                        && if c.symbol == '~' {
+                            o.length == c.length
+                        } else {
+                            !Delimiter::is_multiple_of_three(o, c)
+                        }
+                });
+                match opener {
+                    Some(opener) => {
+                        let used = if c.symbol == '~' {
+                            c.length
+                        } else if delimiters[opener].count >= 2 && delimiters[closer].count >= 2 {
+                            2
+                        } else {
+                            1
+                        };
+                        delimiters[opener].count -= used;
+                        delimiters[opener].opens.push(used);
+                        delimiters[closer].count -= used;
+                        delimiters[closer].closes.push(used);
+                        for d in &mut delimiters[opener + 1..closer] {
+                            d.active = false;
+                        }
+                    }
+                    None => {
+                        openers_bottom.insert(key, closer);
+                        if !delimiters[closer].can_open {
+                            delimiters[closer].active = false;
+                        }
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rebuilds `tokens` with the matched delimiters turned into
+    /// `SpecialToken`s, `DoubleSpecialToken`s and `StrikethroughToken`s. Unmatched characters of a
+    /// run stay as text, between the emphasis it closes and the emphasis it
+    /// opens. Emphasis nested deeper than `MAX_DEPTH` is left as text too.
+    fn build_emphasis(tokens: Vec<InlineToken>, delimiters: Vec<Delimiter>) -> Vec<InlineToken> {
+        let mut stack: Vec<Frame> = Vec::new();
+        let mut result: Vec<InlineToken> = Vec::new();
+        let mut delimiters = delimiters.into_iter().peekable();
+        for (index, token) in tokens.into_iter().enumerate() {
+            let delimiter = match delimiters.next_if(|d| d.index == index) {
+                Some(delimiter) => delimiter,
+                None => {
+                    Delimiter::push(&mut stack, &mut result, token);
+                    continue;
+                }
+            };
+            // The run closes emphasis with its first characters and opens
+            // emphasis with its last ones.
+            let mut offset = token.span().start.offset;
+            for used in &delimiter.closes {
+                offset += used;
+                Delimiter::close(&mut stack, &mut result, offset);
+            }
+            if delimiter.count > 0 {
+                let text = delimiter.symbol.to_string().repeat(delimiter.count);
+                let mut token = InlineToken::get_text_token(text);
+                *token.span_mut() = Span::from_offsets(offset, offset + delimiter.count);
+                Delimiter::push(&mut stack, &mut result, token);
+                offset += delimiter.count;
+            }
+            for used in delimiter.opens.iter().rev() {
+                Delimiter::open(&mut stack, &mut result, delimiter.symbol, *used, offset);
+                offset += used;
+            }
+        }
+        while let Some(frame) = stack.last() {
+            let end = frame
+                .3
+                .last()
+                .map_or(frame.1 + frame.2, |t| t.span().end.offset);
+            Delimiter::close(&mut stack, &mut result, end);
+        }
+        result
+    }
+
+    /// Adds `token` to the innermost emphasis that is still built as a token.
+    /// Emphasis past `MAX_DEPTH` only writes its delimiters as text, so its
+    /// content goes to the emphasis at `MAX_DEPTH` instead.
+    fn push(stack: &mut [Frame], result: &mut Vec<InlineToken>, token: InlineToken) {
+        let depth = stack.len().min(MAX_DEPTH);
+        match stack[..depth].last_mut() {
+            Some(frame) => frame.3.push(token),
+            None => result.push(token),
+        }
+    }
+
+    /// Opens emphasis with the `used` characters of `symbol` at `start`.
+    fn open(
+        stack: &mut Vec<Frame>,
+        result: &mut Vec<InlineToken>,
+        symbol: char,
+        used: usize,
+        start: usize,
+    ) {
+        if stack.len() >= MAX_DEPTH {
+            let text = symbol.to_string().repeat(used);
+            let mut token = InlineToken::get_text_token(text);
+            *token.span_mut() = Span::from_offsets(start, start + used);
+            Delimiter::push(stack, result, token);
+        }
+        stack.push((symbol, start, used, Vec::new()));
+    }
+
+    /// Closes the innermost open emphasis with the characters ending at `end`.
+    fn close(stack: &mut Vec<Frame>, result: &mut Vec<InlineToken>, end: usize) {
+        let (symbol, start, used, inline_tokens) = match stack.pop() {
+            Some(frame) => frame,
+            None => return,
+        };
+        if stack.len() >= MAX_DEPTH {
+            let text = symbol.to_string().repeat(used);
+            let mut token = InlineToken::get_text_token(text);
+            *token.span_mut() = Span::from_offsets(end.saturating_sub(used), end);
+            Delimiter::push(stack, result, token);
+            return;
+        }
+        let span = Span::from_offsets(start, end);
+        let token = if symbol == '~' {
+            let mut t = StrikethroughToken::new(inline_tokens);
+            t.span = span;
+            InlineToken::StrikethroughToken(t)
+        } else if used == 2 {
+            let mut t = DoubleSpecialToken::new(symbol, inline_tokens);
+            t.span = span;
+            InlineToken::DoubleSpecialToken(t)
+        } else {
+            let mut t = SpecialToken::new(symbol, inline_tokens);
+            t.span = span;
+            InlineToken::SpecialToken(t)
+        };
+        Delimiter::push(stack, result, token);
+    }
+}
+
+/// Emphasis being built: its symbol, the offset and number of its opening
+/// characters, and the tokens inside it so far.
+type Frame = (char, usize, usize, Vec<InlineToken>);
+
+#[derive(Debug, Clone)]
 pub struct TextToken {
     pub text: String,
+    pub span: Span,
 }
-#[derive(Debug)]
+
+impl TextToken {
+    pub fn new(text: String, span: Span) -> Self {
+        Self { text, span }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct SpecialToken {
     pub token: char,
     pub inline_tokens: Vec<InlineToken>,
+    pub span: Span,
 }
 
 impl SpecialToken {
@@ -124,32 +475,46 @@ impl SpecialToken {
         Self {
             token,
             inline_tokens,
+            span: Span::default(),
         }
     }
 
-    pub fn try_tokenize(text: &str, symbol: char, re_symbol: &str) -> (Option<InlineToken>, usize) {
-        let re = Regex::new(&format!(
-            r"^{}([^{}]+?[^\\]{})",
-            re_symbol, symbol, re_symbol
-        ))
-        .unwrap();
-        let caps = re.captures(text);
-        if let Some(mat) = caps {
-            let inner_text = mat.get(1).unwrap().as_str();
-            let token = SpecialToken::new(
-                symbol,
-                InlineToken::tokenizer(&inner_text[..inner_text.len() - 1]),
-            );
-            (Some(InlineToken::SpecialToken(token)), inner_text.len() + 1)
-        } else {
-            (None, 0)
+    /// Tokenizes the code span at the start of `text`, which starts with a
+    /// run of backticks and ends at the next run of the same length. Newlines
+    /// in the code become spaces, and one space is stripped from both ends if
+    /// there is one on each side. If the run is never closed, returns `None`
+    /// and the length of the run, which is literal text.
+    pub fn try_tokenize_code(text: &str) -> (Option<InlineToken>, usize) {
+        let length = text.len() - text.trim_start_matches('`').len();
+        let mut i = length;
+        while let Some(offset) = text[i..].find('`') {
+            let start = i + offset;
+            let run = text[start..].len() - text[start..].trim_start_matches('`').len();
+            if run == length {
+                let mut code = text[length..start].replace('\n', " ");
+                let mut span = Span::from_offsets(length, start);
+                if code.len() >= 2
+                    && code.starts_with(' ')
+                    && code.ends_with(' ')
+                    && !code.trim_matches(' ').is_empty()
+                {
+                    code = code[1..code.len() - 1].to_string();
+                    span = Span::from_offsets(length + 1, start - 1);
+                }
+                let inline_tokens = vec![InlineToken::TextToken(TextToken::new(code, span))];
+                let token = InlineToken::SpecialToken(SpecialToken::new('`', inline_tokens));
+                return (Some(token), start + run);
+            }
+            i = start + run;
         }
+        (None, length)
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct DoubleSpecialToken {
     pub token: char,
     pub inline_tokens: Vec<InlineToken>,
+    pub span: Span,
 }
 
 impl DoubleSpecialToken {
@@ -157,32 +522,33 @@ impl DoubleSpecialToken {
         Self {
             token,
             inline_tokens,
+            span: Span::default(),
         }
     }
+}
 
-    pub fn try_tokenize(text: &str, symbol: char, re_symbol: &str) -> (Option<InlineToken>, usize) {
-        let re = Regex::new(&format!(r"^{}{{2}}(.+?[^\\]{}{{2}})", re_symbol, re_symbol)).unwrap();
-        let caps = re.captures(text);
-        if let Some(mat) = caps {
-            let inner_text = mat.get(1).unwrap().as_str();
-            let token = DoubleSpecialToken::new(
-                symbol,
-                InlineToken::tokenizer(&inner_text[..inner_text.len() - 2]),
-            );
-            (
-                Some(InlineToken::DoubleSpecialToken(token)),
-                inner_text.len() + 2,
-            )
-        } else {
-            (None, 0)
+/// Text struck through with `~` or `~~`.
+#[derive(Debug, Clone)]
+pub struct StrikethroughToken {
+    pub inline_tokens: Vec<InlineToken>,
+    pub span: Span,
+}
+
+impl StrikethroughToken {
+    pub fn new(inline_tokens: Vec<InlineToken>) -> Self {
+        Self {
+            inline_tokens,
+            span: Span::default(),
         }
     }
 }
-#[derive(Debug)]
+
+#[derive(Debug, Clone)]
 pub struct LinkToken {
     pub alt: String,
     pub link: String,
     pub need_note: bool,
+    pub span: Span,
 }
 
 impl LinkToken {
@@ -191,51 +557,186 @@ impl LinkToken {
             alt,
             link,
             need_note,
+            span: Span::default(),
         }
     }
 
+    /// Length in bytes of the source text this token was built from.
     pub fn len(&self) -> usize {
         self.alt.len() + self.link.len() + 4
     }
 
+    /// Whether the token was built from no text, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn try_tokenize_with_real_link(text: &str) -> Option<LinkToken> {
-        let re = Regex::new(r"\[(.*?)\]\((.*?)\)").unwrap();
-        let caps = re.captures(text);
-        if let Some(mat) = caps {
-            let (alt, link) = InlineToken::get_alt_and_link(&mat);
-            Some(LinkToken::new(alt, link, false))
-        } else {
-            None
-        }
+        let (alt, link) = LinkScanner::new(text).alt_and_link(0, "[", false)?;
+        Some(LinkToken::new(alt, link, false))
     }
 
     pub fn try_tokenize_with_need_note(text: &str) -> Option<LinkToken> {
-        let re = Regex::new(r"\[(.*?)\]\[(.*?)\]").unwrap();
-        let caps = re.captures(text);
-        if let Some(mat) = caps {
-            let (alt, link) = InlineToken::get_alt_and_link(&mat);
-            Some(LinkToken::new(alt, link, true))
-        } else {
-            None
-        }
+        let (alt, link) = LinkScanner::new(text).alt_and_link(0, "[", true)?;
+        Some(LinkToken::new(alt, link, true))
     }
 
     pub fn try_tokenize(text: &str) -> Option<LinkToken> {
-        if let Some(token) = LinkToken::try_tokenize_with_real_link(text) {
-            Some(token)
-        } else if let Some(token) = LinkToken::try_tokenize_with_need_note(text) {
-            Some(token)
+        LinkToken::try_tokenize_with_real_link(text)
+            .or_else(|| LinkToken::try_tokenize_with_need_note(text))
+    }
+
+    /// Tokenizes the link whose `[` is at byte `start` of the text of
+    /// `links`, like `try_tokenize`.
+    fn try_tokenize_at(links: &mut LinkScanner, start: usize) -> Option<LinkToken> {
+        [false, true].iter().find_map(|&need_note| {
+            let (alt, link) = links.alt_and_link(start, "[", need_note)?;
+            Some(LinkToken::new(alt, link, need_note))
+        })
+    }
+
+    /// Tokenizes the autolink at the start of `text`: an absolute URI or an
+    /// email address between `<` and `>`. Returns it with the length of its
+    /// source text. The link of an email address gets a `mailto:` scheme.
+    pub fn try_tokenize_autolink(text: &str) -> Option<(LinkToken, usize)> {
+        let uri = regex!(r"^<([A-Za-z][A-Za-z0-9+.\-]{1,31}:[^\x00-\x20<>]*)>");
+        if let Some(caps) = uri.captures(text) {
+            let uri = InlineToken::get_nth_cap(&caps, 1);
+            return Some((LinkToken::new(uri.clone(), uri, false), caps[0].len()));
+        }
+        let email = regex!(
+            r"^<([a-zA-Z0-9.!#$%&'*+/=?^_`{|}~\-]+@[a-zA-Z0-9](?:[a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?(?:\.[a-zA-Z0-9](?:[a-zA-Z0-9\-]{0,61}[a-zA-Z0-9])?)*)>"
+        );
+        let caps = email.captures(text)?;
+        let address = InlineToken::get_nth_cap(&caps, 1);
+        let link = format!("mailto:{}", address);
+        Some((LinkToken::new(address, link, false), caps[0].len()))
+    }
+
+    /// Tokenizes the extended autolink starting at byte `start` of `text`, if
+    /// `extensions` has autolinks: a `www.` address, an `http://` or
+    /// `https://` URL, or an email address, with no `<` and `>` around it.
+    /// Returns it with the length of its source text, and `None` if `start`
+    /// is inside a multibyte character.
+    ///
+    /// As in GFM, a `www.` address or URL must follow whitespace, `*`, `_`, `~`
+    /// or `(`, and ends before whitespace or `<`. Trailing punctuation, a `)`
+    /// without a matching `(` and a trailing entity like `&amp;` are left out.
+    pub fn try_tokenize_extended(
+        text: &str,
+        start: usize,
+        extensions: Extensions,
+    ) -> Option<(LinkToken, usize)> {
+        if !extensions.autolinks || !text.is_char_boundary(start) {
+            return None;
+        }
+        LinkToken::try_extended(text, start)
+    }
+
+    #[cfg(feature = "autolinks")]
+    fn try_extended(text: &str, start: usize) -> Option<(LinkToken, usize)> {
+        let before = text[..start].chars().next_back();
+        let rest = &text[start..];
+        let (scheme, host) = if rest.starts_with("www.") {
+            ("http://", 0)
+        } else if rest.starts_with("http://") {
+            ("", 7)
+        } else if rest.starts_with("https://") {
+            ("", 8)
         } else {
-            None
+            return LinkToken::try_extended_email(before, rest);
+        };
+        if !before.map_or(true, |c| c.is_whitespace() || "*_~(".contains(c)) {
+            return None;
         }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '<')
+            .unwrap_or(rest.len());
+        let url = LinkToken::trim_autolink(&rest[..end]);
+        let domain = url[host..]
+            .split(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+            .next()?;
+        if !LinkToken::is_valid_domain(domain) {
+            return None;
+        }
+        let link = format!("{}{}", scheme, url);
+        Some((LinkToken::new(url.to_string(), link, false), url.len()))
+    }
+
+    #[cfg(not(feature = "autolinks"))]
+    fn try_extended(_text: &str, _start: usize) -> Option<(LinkToken, usize)> {
+        None
+    }
+
+    /// An email address at the start of `rest`, which doesn't continue the
+    /// local part of an address ending with the `before` character.
+    #[cfg(feature = "autolinks")]
+    fn try_extended_email(before: Option<char>, rest: &str) -> Option<(LinkToken, usize)> {
+        let is_local = |c: char| c.is_ascii_alphanumeric() || ".-_+".contains(c);
+        if before.is_some_and(is_local) {
+            return None;
+        }
+        let at = rest.find(|c: char| !is_local(c))?;
+        if at == 0 || !rest[at..].starts_with('@') {
+            return None;
+        }
+        let domain = rest[at + 1..]
+            .split(|c: char| !(c.is_ascii_alphanumeric() || "._-".contains(c)))
+            .next()?
+            .trim_end_matches('.');
+        if !domain.contains('.') || domain.ends_with(['-', '_']) {
+            return None;
+        }
+        let address = &rest[..at + 1 + domain.len()];
+        let link = format!("mailto:{}", address);
+        Some((
+            LinkToken::new(address.to_string(), link, false),
+            address.len(),
+        ))
+    }
+
+    /// `url` without its trailing punctuation, unmatched `)` and entity.
+    #[cfg(feature = "autolinks")]
+    fn trim_autolink(mut url: &str) -> &str {
+        loop {
+            let trimmed = match url.chars().next_back() {
+                Some('?' | '!' | '.' | ',' | ':' | '*' | '_' | '~') => &url[..url.len() - 1],
+                Some(')') if url.matches('(').count() < url.matches(')').count() => {
+                    &url[..url.len() - 1]
+                }
+                Some(';') => {
+                    let name =
+                        url[..url.len() - 1].trim_end_matches(|c: char| c.is_ascii_alphanumeric());
+                    match name.strip_suffix('&') {
+                        Some(entity) if name.len() < url.len() - 1 => entity,
+                        _ => &url[..url.len() - 1],
+                    }
+                }
+                _ => return url,
+            };
+            url = trimmed;
+        }
+    }
+
+    /// Whether `domain` has a period, and no underscore in its last two
+    /// segments.
+    #[cfg(feature = "autolinks")]
+    fn is_valid_domain(domain: &str) -> bool {
+        domain.contains('.')
+            && domain.split('.').all(|segment| !segment.is_empty())
+            && !domain
+                .rsplit('.')
+                .take(2)
+                .any(|segment| segment.contains('_'))
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ImageToken {
     pub alt: String,
     pub link: String,
     pub need_note: bool,
+    pub span: Span,
 }
 
 impl ImageToken {
@@ -244,41 +745,129 @@ impl ImageToken {
             alt,
             link,
             need_note,
+            span: Span::default(),
         }
     }
 
+    /// Length in bytes of the source text this token was built from.
     pub fn len(&self) -> usize {
         self.alt.len() + self.link.len() + 5
     }
 
+    /// Whether the token was built from no text, which never happens.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
     pub fn try_tokenize_with_real_link(text: &str) -> Option<ImageToken> {
-        let re = Regex::new(r"!\[(.*?)\]\((.*?)\)").unwrap();
-        let caps = re.captures(text);
-        if let Some(mat) = caps {
-            let (alt, link) = InlineToken::get_alt_and_link(&mat);
-            return Some(ImageToken::new(alt, link, false));
-        }
-        None
+        let (alt, link) = LinkScanner::new(text).alt_and_link(0, "![", false)?;
+        Some(ImageToken::new(alt, link, false))
     }
 
     pub fn try_tokenize_with_need_note(text: &str) -> Option<ImageToken> {
-        let re = Regex::new(r"!\[(.*?)\]\[(.*?)\]").unwrap();
-        let caps = re.captures(text);
-        if let Some(mat) = caps {
-            let (alt, link) = InlineToken::get_alt_and_link(&mat);
-            return Some(ImageToken::new(alt, link, true));
-        }
-        None
+        let (alt, link) = LinkScanner::new(text).alt_and_link(0, "![", true)?;
+        Some(ImageToken::new(alt, link, true))
     }
 
     pub fn try_tokenize(text: &str) -> Option<ImageToken> {
-        if let Some(token) = ImageToken::try_tokenize_with_real_link(text) {
-            Some(token)
-        } else if let Some(token) = ImageToken::try_tokenize_with_need_note(text) {
-            Some(token)
+        ImageToken::try_tokenize_with_real_link(text)
+            .or_else(|| ImageToken::try_tokenize_with_need_note(text))
+    }
+
+    /// Tokenizes the image whose `![` is at byte `start` of the text of
+    /// `links`, like `try_tokenize`.
+    fn try_tokenize_at(links: &mut LinkScanner, start: usize) -> Option<ImageToken> {
+        [false, true].iter().find_map(|&need_note| {
+            let (alt, link) = links.alt_and_link(start, "![", need_note)?;
+            Some(ImageToken::new(alt, link, need_note))
+        })
+    }
+}
+
+/// Finds where the links and images of a text end: the `](` or `][` after
+/// their alt text, and the `)` or `]` after their link, on the same line.
+///
+/// The tokenizer tries a link at every `[` it meets, left to right, so each
+/// search starts at or after the previous one. The scanner remembers the last
+/// match of every pattern and only searches again once it is passed, which
+/// reads the text about once in all instead of once per `[`.
+struct LinkScanner<'a> {
+    text: &'a str,
+    newline: NextMatch,
+    paren_middle: NextMatch,
+    bracket_middle: NextMatch,
+    paren: NextMatch,
+    bracket: NextMatch,
+}
+
+impl<'a> LinkScanner<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            text,
+            newline: NextMatch::new("\n"),
+            paren_middle: NextMatch::new("]("),
+            bracket_middle: NextMatch::new("]["),
+            paren: NextMatch::new(")"),
+            bracket: NextMatch::new("]"),
+        }
+    }
+
+    /// The alt text and link of the link or image that starts with `opener`
+    /// at byte `start`: `[alt](link)`, or `[alt][label]` if `need_note` is
+    /// set. The alt text ends at the first `](` or `][` of the line, which
+    /// must have a `)` or `]` after it on that line.
+    fn alt_and_link(
+        &mut self,
+        start: usize,
+        opener: &str,
+        need_note: bool,
+    ) -> Option<(String, String)> {
+        let text = self.text;
+        if !text[start..].starts_with(opener) {
+            return None;
+        }
+        let alt_start = start + opener.len();
+        let line_end = self.newline.find(text, alt_start).unwrap_or(text.len());
+        let (middle, close) = if need_note {
+            (&mut self.bracket_middle, &mut self.bracket)
         } else {
-            None
+            (&mut self.paren_middle, &mut self.paren)
+        };
+        let alt_end = middle.find(text, alt_start).filter(|&e| e < line_end)?;
+        let link_start = alt_end + 2;
+        let link_end = close.find(text, link_start).filter(|&e| e < line_end)?;
+        let alt = text[alt_start..alt_end].to_string();
+        Some((alt, text[link_start..link_end].to_string()))
+    }
+}
+
+/// The first match of `pattern` at or after the offset of the last search,
+/// kept until a search starts past it.
+struct NextMatch {
+    pattern: &'static str,
+    from: Option<usize>,
+    found: Option<usize>,
+}
+
+impl NextMatch {
+    fn new(pattern: &'static str) -> Self {
+        Self {
+            pattern,
+            from: None,
+            found: None,
+        }
+    }
+
+    /// The offset of the first match of the pattern in `text` at or after
+    /// `offset`, which must be on a char boundary.
+    fn find(&mut self, text: &str, offset: usize) -> Option<usize> {
+        let reusable = self.from.is_some_and(|from| from <= offset)
+            && self.found.map_or(true, |found| found >= offset);
+        if !reusable {
+            self.from = Some(offset);
+            self.found = text[offset..].find(self.pattern).map(|i| i + offset);
         }
+        self.found
     }
 }
 
@@ -298,7 +887,7 @@ pub mod tests {
     pub fn assert_special_token_group(token: &InlineToken, text: &str, symbol: char) {
         assert_special_token(token, symbol);
         if let InlineToken::SpecialToken(t) = token {
-            assert_eq!(t.inline_tokens.len(), 1 as usize);
+            assert_eq!(t.inline_tokens.len(), 1);
             assert_text_token(&t.inline_tokens[0], text);
         } else {
             panic!();
@@ -317,7 +906,7 @@ pub mod tests {
     pub fn assert_double_special_token_group(token: &InlineToken, text: &str, symbol: char) {
         assert_double_special_token(token, symbol);
         if let InlineToken::DoubleSpecialToken(t) = token {
-            assert_eq!(t.inline_tokens.len(), 1 as usize);
+            assert_eq!(t.inline_tokens.len(), 1);
             assert_text_token(&t.inline_tokens[0], text);
         } else {
             println!("{:?}", token);
@@ -338,8 +927,8 @@ pub mod tests {
     fn test_two_asterisk() {
         let text = "**";
         let result = InlineToken::tokenizer(text);
-        assert_text_token(&result[0], "*");
-        assert_text_token(&result[1], "*");
+        assert_eq!(result.len(), 1);
+        assert_text_token(&result[0], "**");
     }
 
     #[test]
@@ -376,7 +965,7 @@ pub mod tests {
         let token = &result[0];
         assert_special_token(token, '*');
         if let InlineToken::SpecialToken(t) = token {
-            assert_eq!(t.inline_tokens.len(), 2 as usize);
+            assert_eq!(t.inline_tokens.len(), 2);
             assert_text_token(&t.inline_tokens[0], r"Test\");
             assert_text_token(&t.inline_tokens[1], "*");
         } else {
@@ -401,7 +990,7 @@ pub mod tests {
         let result = InlineToken::tokenizer(text);
         assert_text_token(&result[0], r"*");
         if let InlineToken::SpecialToken(t) = &result[1] {
-            assert_eq!(t.inline_tokens.len(), 2 as usize);
+            assert_eq!(t.inline_tokens.len(), 2);
             assert_text_token(&t.inline_tokens[0], r"Test\");
             assert_text_token(&t.inline_tokens[1], "*");
         } else {
@@ -415,7 +1004,7 @@ pub mod tests {
         let text = r"*_Test*_";
         let result = InlineToken::tokenizer(text);
         if let InlineToken::SpecialToken(t) = &result[0] {
-            assert_eq!(t.inline_tokens.len(), 2 as usize);
+            assert_eq!(t.inline_tokens.len(), 2);
             assert_text_token(&t.inline_tokens[0], r"_");
             assert_text_token(&t.inline_tokens[1], "Test");
         } else {
@@ -515,6 +1104,20 @@ pub mod tests {
         assert_text_token(&result[2], " to test");
     }
 
+    #[test]
+    fn test_link_token_ends_with_its_line() {
+        let result = LinkToken::try_tokenize("[a] b](c) d)").unwrap();
+        assert_eq!((result.alt.as_str(), result.link.as_str()), ("a] b", "c"));
+        assert!(LinkToken::try_tokenize("[a\n](b)").is_none());
+        assert!(LinkToken::try_tokenize("[a](b\n)").is_none());
+        assert!(ImageToken::try_tokenize("![a][b\n]").is_none());
+        assert!(LinkToken::try_tokenize(&"[".repeat(100)).is_none());
+        let result = InlineToken::tokenizer("[a [b](c) ![d][e]\n[f](g) [h]");
+        assert_link_token(&result[0], "a [b", "c", false);
+        assert_image_token(&result[2], "d", "e", true);
+        assert_link_token(&result[4], "f", "g", false);
+    }
+
     #[test]
     fn test_image_token() {
         let text = "![Link](http://a.com)";
@@ -553,4 +1156,357 @@ pub mod tests {
         assert_text_token(&result[4], "`");
     }
 
+    #[test]
+    fn test_special_tokens_around_multibyte_text() {
+        let result = InlineToken::tokenizer("*café*");
+        assert_eq!(result.len(), 1);
+        assert_special_token_group(&result[0], "café", '*');
+
+        let result = InlineToken::tokenizer("_日本語_");
+        assert_eq!(result.len(), 1);
+        assert_special_token_group(&result[0], "日本語", '_');
+
+        let result = InlineToken::tokenizer("こんにちは **世界** 🎉");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "こんにちは ");
+        assert_double_special_token_group(&result[1], "世界", '*');
+        assert_text_token(&result[2], " 🎉");
+    }
+
+    #[test]
+    fn test_link_and_image_with_multibyte_text() {
+        let result = InlineToken::tokenizer("voir [café](http://a.com/é) ici");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "voir ");
+        assert_link_token(&result[1], "café", "http://a.com/é", false);
+        assert_text_token(&result[2], " ici");
+
+        let result = InlineToken::tokenizer("🎉![图片][图]ü");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "🎉");
+        assert_image_token(&result[1], "图片", "图", true);
+        assert_text_token(&result[2], "ü");
+    }
+
+    #[test]
+    fn test_unused_special_tokens_next_to_multibyte_text() {
+        let result = InlineToken::tokenizer("é[ñ!ü*😀_");
+        assert_eq!(result.len(), 8);
+        assert_text_token(&result[0], "é");
+        assert_text_token(&result[1], "[");
+        assert_text_token(&result[2], "ñ");
+        assert_text_token(&result[3], "!");
+        assert_text_token(&result[4], "ü");
+        assert_text_token(&result[5], "*");
+        assert_text_token(&result[6], "😀");
+        assert_text_token(&result[7], "_");
+    }
+
+    #[test]
+    fn test_escape_after_multibyte_text() {
+        let result = InlineToken::tokenizer("é\\*ü*");
+        assert_text_token(&result[0], "é\\");
+        assert_text_token(&result[1], "*ü");
+        assert_text_token(&result[2], "*");
+    }
+
+    #[test]
+    fn test_newline_is_soft_break() {
+        let result = InlineToken::tokenizer("foo \nbar");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "foo");
+        assert!(matches!(&result[1], InlineToken::SoftBreak(_)));
+        assert_text_token(&result[2], "bar");
+    }
+
+    #[test]
+    fn test_hard_break_from_trailing_spaces() {
+        let result = InlineToken::tokenizer("foo   \nbar");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "foo");
+        assert!(matches!(&result[1], InlineToken::BreakToken(_)));
+        assert_text_token(&result[2], "bar");
+    }
+
+    #[test]
+    fn test_hard_break_from_backslash() {
+        let result = InlineToken::tokenizer("foo\\\nbar");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "foo");
+        assert!(matches!(&result[1], InlineToken::BreakToken(_)));
+        assert_text_token(&result[2], "bar");
+    }
+
+    #[test]
+    fn test_break_after_special_token() {
+        let result = InlineToken::tokenizer("**foo**\nbar");
+        assert_eq!(result.len(), 3);
+        assert_double_special_token_group(&result[0], "foo", '*');
+        assert!(matches!(&result[1], InlineToken::SoftBreak(_)));
+        assert_text_token(&result[2], "bar");
+    }
+
+    #[test]
+    fn test_triple_delimiters() {
+        let result = InlineToken::tokenizer("***both***");
+        assert_eq!(result.len(), 1);
+        assert_special_token(&result[0], '*');
+        if let InlineToken::SpecialToken(t) = &result[0] {
+            assert_eq!(t.inline_tokens.len(), 1);
+            assert_double_special_token_group(&t.inline_tokens[0], "both", '*');
+        }
+    }
+
+    #[test]
+    fn test_intraword_underscore() {
+        let result = InlineToken::tokenizer("snake_case_name");
+        assert_eq!(result.len(), 5);
+        assert_text_token(&result[1], "_");
+        assert_text_token(&result[3], "_");
+
+        let result = InlineToken::tokenizer("foo*bar*");
+        assert_eq!(result.len(), 2);
+        assert_special_token_group(&result[1], "bar", '*');
+    }
+
+    #[test]
+    fn test_nested_emphasis() {
+        let result = InlineToken::tokenizer("*a **b** c*");
+        assert_eq!(result.len(), 1);
+        if let InlineToken::SpecialToken(t) = &result[0] {
+            assert_eq!(t.inline_tokens.len(), 3);
+            assert_text_token(&t.inline_tokens[0], "a ");
+            assert_double_special_token_group(&t.inline_tokens[1], "b", '*');
+            assert_text_token(&t.inline_tokens[2], " c");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_unbalanced_delimiters() {
+        let result = InlineToken::tokenizer("**foo*");
+        assert_eq!(result.len(), 2);
+        assert_text_token(&result[0], "*");
+        assert_special_token_group(&result[1], "foo", '*');
+
+        let result = InlineToken::tokenizer("*foo**");
+        assert_eq!(result.len(), 2);
+        assert_special_token_group(&result[0], "foo", '*');
+        assert_text_token(&result[1], "*");
+
+        let result = InlineToken::tokenizer("a * b *");
+        assert_eq!(result.len(), 4);
+        assert_text_token(&result[1], "*");
+        assert_text_token(&result[3], "*");
+    }
+
+    #[test]
+    fn test_multiple_of_three_rule() {
+        let result = InlineToken::tokenizer("*foo**bar*");
+        assert_eq!(result.len(), 1);
+        if let InlineToken::SpecialToken(t) = &result[0] {
+            assert_eq!(t.inline_tokens.len(), 3);
+            assert_text_token(&t.inline_tokens[1], "**");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_code_span_takes_precedence() {
+        let result = InlineToken::tokenizer("*a `*` b*");
+        assert_eq!(result.len(), 1);
+        if let InlineToken::SpecialToken(t) = &result[0] {
+            assert_eq!(t.inline_tokens.len(), 3);
+            assert_special_token_group(&t.inline_tokens[1], "*", '`');
+        } else {
+            panic!();
+        }
+
+        let result = InlineToken::tokenizer("`` a`b ``");
+        assert_eq!(result.len(), 1);
+        assert_special_token_group(&result[0], "a`b", '`');
+
+        let result = InlineToken::tokenizer("``a`");
+        assert_eq!(result.len(), 3);
+        assert_text_token(&result[0], "``");
+    }
+
+    fn source<'a>(text: &'a str, token: &InlineToken) -> &'a str {
+        let span = token.span();
+        &text[span.start.offset..span.end.offset]
+    }
+
+    #[test]
+    fn test_spans() {
+        let text = "a *b* `c` [d](e)\n**f**";
+        let result = InlineToken::tokenizer(text);
+        let sources: Vec<&str> = result.iter().map(|t| source(text, t)).collect();
+        assert_eq!(
+            sources,
+            vec!["a ", "*b*", " ", "`c`", " ", "[d](e)", "\n", "**f**"]
+        );
+        match &result[1] {
+            InlineToken::SpecialToken(t) => assert_eq!(source(text, &t.inline_tokens[0]), "b"),
+            _ => panic!(),
+        }
+        match &result[3] {
+            InlineToken::SpecialToken(t) => assert_eq!(source(text, &t.inline_tokens[0]), "c"),
+            _ => panic!(),
+        }
+        let span = result[7].span();
+        assert_eq!(span.start, Position::new(17, 2, 1));
+        assert_eq!(span.end, Position::new(22, 2, 6));
+    }
+
+    #[test]
+    fn test_spans_of_partly_matched_delimiters() {
+        let text = "***a** b*";
+        let result = InlineToken::tokenizer(text);
+        assert_eq!(source(text, &result[0]), "***a** b*");
+        match &result[0] {
+            InlineToken::SpecialToken(t) => assert_eq!(source(text, &t.inline_tokens[0]), "**a**"),
+            _ => panic!(),
+        }
+        let text = "**a*";
+        let result = InlineToken::tokenizer(text);
+        assert_eq!(source(text, &result[0]), "*");
+        assert_eq!(source(text, &result[1]), "*a*");
+    }
+
+    #[test]
+    fn test_spans_of_line_breaks() {
+        let text = "a  \nb\\\nc\nd";
+        let result = InlineToken::tokenizer(text);
+        let sources: Vec<&str> = result.iter().map(|t| source(text, t)).collect();
+        assert_eq!(sources, vec!["a", "  \n", "b", "\\\n", "c", "\n", "d"]);
+    }
+
+    pub fn assert_strikethrough_group(token: &InlineToken, text: &str) {
+        if let InlineToken::StrikethroughToken(t) = token {
+            assert_eq!(t.inline_tokens.len(), 1);
+            assert_text_token(&t.inline_tokens[0], text);
+        } else {
+            println!("{:?}", token);
+            panic!();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "strikethrough")]
+    fn test_strikethrough() {
+        let result = InlineToken::tokenizer_with("~~a~~ ~b~", Extensions::gfm());
+        assert_eq!(result.len(), 3);
+        assert_strikethrough_group(&result[0], "a");
+        assert_text_token(&result[1], " ");
+        assert_strikethrough_group(&result[2], "b");
+    }
+
+    #[test]
+    #[cfg(feature = "strikethrough")]
+    fn test_strikethrough_runs_must_match() {
+        for text in ["~a~~", "~~a~", "~~~a~~~", "~~ a ~~", "\\~a~"] {
+            let result = InlineToken::tokenizer_with(text, Extensions::gfm());
+            let struck = result
+                .iter()
+                .any(|t| matches!(t, InlineToken::StrikethroughToken(_)));
+            assert!(!struck, "{}: {:?}", text, result);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "strikethrough")]
+    fn test_strikethrough_and_emphasis_nest() {
+        let text = "*~~a~~* ~~**b** c~~";
+        let result = InlineToken::tokenizer_with(text, Extensions::gfm());
+        assert_eq!(result.len(), 3);
+        match &result[0] {
+            InlineToken::SpecialToken(t) => assert_strikethrough_group(&t.inline_tokens[0], "a"),
+            t => panic!("{:?}", t),
+        }
+        match &result[2] {
+            InlineToken::StrikethroughToken(t) => {
+                assert_double_special_token_group(&t.inline_tokens[0], "b", '*');
+                assert_text_token(&t.inline_tokens[1], " c");
+                assert_eq!(source(text, &result[2]), "~~**b** c~~");
+                assert_eq!(source(text, &t.inline_tokens[0]), "**b**");
+            }
+            t => panic!("{:?}", t),
+        }
+    }
+
+    #[test]
+    fn test_autolink() {
+        let text = "a <http://a.com/b?c> <foo@bar.com> <not a link>";
+        let result = InlineToken::tokenizer(text);
+        assert_eq!(result.len(), 7);
+        assert_link_token(&result[1], "http://a.com/b?c", "http://a.com/b?c", false);
+        assert_eq!(source(text, &result[1]), "<http://a.com/b?c>");
+        assert_link_token(&result[3], "foo@bar.com", "mailto:foo@bar.com", false);
+        assert_text_token(&result[5], "<");
+        assert_text_token(&result[6], "not a link>");
+    }
+
+    #[test]
+    #[cfg(feature = "autolinks")]
+    fn test_extended_autolink() {
+        let text = "see www.a.com/b, (https://a.com/c_(d)) or a.b+c@d.org.";
+        let result = InlineToken::tokenizer_with(text, Extensions::gfm());
+        assert_eq!(result.len(), 7);
+        assert_text_token(&result[0], "see ");
+        assert_link_token(&result[1], "www.a.com/b", "http://www.a.com/b", false);
+        assert_eq!(source(text, &result[1]), "www.a.com/b");
+        assert_text_token(&result[2], ", (");
+        assert_link_token(
+            &result[3],
+            "https://a.com/c_(d)",
+            "https://a.com/c_(d)",
+            false,
+        );
+        assert_text_token(&result[4], ") or ");
+        assert_link_token(&result[5], "a.b+c@d.org", "mailto:a.b+c@d.org", false);
+        assert_text_token(&result[6], ".");
+    }
+
+    #[test]
+    #[cfg(feature = "autolinks")]
+    fn test_not_extended_autolink() {
+        let texts = [
+            "www.a_b.c_d",
+            "http://localhost",
+            "a-www.a.com",
+            "<www.a.com",
+            "a.b@c.d_",
+            "a@b",
+        ];
+        for text in texts {
+            let result = InlineToken::tokenizer_with(text, Extensions::gfm());
+            let linked = result
+                .iter()
+                .any(|t| matches!(t, InlineToken::LinkToken(_)));
+            assert!(!linked, "{}: {:?}", text, result);
+        }
+        let result = InlineToken::tokenizer("www.a.com a@b.com");
+        assert_eq!(result.len(), 1);
+    }
+
+    #[test]
+    #[cfg(feature = "autolinks")]
+    fn test_trim_autolink() {
+        assert_eq!(LinkToken::trim_autolink("a.com/b?!.,:*_~"), "a.com/b");
+        assert_eq!(LinkToken::trim_autolink("a.com/(b))"), "a.com/(b)");
+        assert_eq!(LinkToken::trim_autolink("a.com/?q=c&hl;"), "a.com/?q=c");
+        assert_eq!(
+            LinkToken::trim_autolink("a.com/?q=c&hl=en"),
+            "a.com/?q=c&hl=en"
+        );
+    }
+
+    #[test]
+    fn test_tilde_is_text_without_strikethrough() {
+        let result = InlineToken::tokenizer("~~a~~");
+        assert_eq!(result.len(), 1);
+        assert_text_token(&result[0], "~~a~~");
+    }
 }
@@ -1,8 +1,10 @@
-use super::inline_token::InlineToken;
+use super::inline_token::{InlineToken, SpecialToken};
+use super::span::{Position, Span};
+use super::{Error, Extensions, Tokenizer, MAX_DEPTH};
 extern crate regex;
-use regex::Regex;
+use std::borrow::Cow;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum LineToken {
     HeaderToken(HeaderToken),
     Paragraph(Paragraph),
@@ -13,54 +15,311 @@ pub enum LineToken {
     OrderedList(OrderedList),
     UnorderedList(UnorderedList),
     NoteToken(NoteToken),
-    HorizontalRule,
+    HorizontalRule(Span),
+    Table(Table),
 }
 
 const NOT_LIST: char = 'a';
 
+/// Moves a position in text made of the lines from `index` on, each with
+/// `shifts[i]` bytes cut from its start, to the same place in those lines.
+fn shift_position(p: Position, index: usize, shifts: &[isize]) -> Position {
+    let shift = shifts.get(p.line.wrapping_sub(1)).copied().unwrap_or(0);
+    Position {
+        line: index + p.line,
+        column: (p.column as isize + shift).max(1) as usize,
+        ..p
+    }
+}
+
+/// Moves the spans of `tokens` from `inner`, lines cut from the end of
+/// `lines[index..]`, to `lines`.
+fn shift_spans(tokens: &mut [LineToken], lines: &[&str], inner: &[&str], index: usize) {
+    let shifts: Vec<isize> = inner
+        .iter()
+        .zip(&lines[index..])
+        .map(|(inner, line)| line.len() as isize - inner.len() as isize)
+        .collect();
+    for token in tokens {
+        token.map_spans(&|p| shift_position(p, index, &shifts));
+    }
+}
+
+/// Moves the spans of inline tokens from text made of the lines from `index`
+/// on, each with `shifts[i]` bytes cut from its start, to those lines.
+fn shift_inline_spans(tokens: &mut [InlineToken], index: usize, shifts: &[isize]) {
+    for token in tokens {
+        token.map_spans(&|p| shift_position(p, index, shifts));
+    }
+}
+
+/// The marker that starts a list item.
+#[derive(Debug, Clone)]
+pub struct ListMarker {
+    /// The number of an ordered list item, `None` for a bullet.
+    pub order: Option<usize>,
+    pub symbol: char,
+    /// Byte offset of the item's content in the line.
+    pub offset: usize,
+    /// Whether the item has no content on the marker's line.
+    pub is_empty: bool,
+}
+
 impl LineToken {
-    pub fn is_horizontal_rule(line: &str) -> bool {
-        let re = Regex::new("^([-]{3,}|[*]{3,})$").unwrap();
-        if let Some(_) = re.captures(line) {
-            true
-        } else {
-            false
+    /// The source text the token was built from.
+    pub fn span(&self) -> Span {
+        match self {
+            LineToken::HeaderToken(t) => t.span,
+            LineToken::Paragraph(t) => t.span,
+            LineToken::CodeBlock(t) => t.span,
+            LineToken::Quote(t) => t.span,
+            LineToken::OrderedListBlock(t) => t.span,
+            LineToken::UnorderedListBlock(t) => t.span,
+            LineToken::OrderedList(t) => t.span,
+            LineToken::UnorderedList(t) => t.span,
+            LineToken::NoteToken(t) => t.span,
+            LineToken::HorizontalRule(span) => *span,
+            LineToken::Table(t) => t.span,
+        }
+    }
+
+    pub fn span_mut(&mut self) -> &mut Span {
+        match self {
+            LineToken::HeaderToken(t) => &mut t.span,
+            LineToken::Paragraph(t) => &mut t.span,
+            LineToken::CodeBlock(t) => &mut t.span,
+            LineToken::Quote(t) => &mut t.span,
+            LineToken::OrderedListBlock(t) => &mut t.span,
+            LineToken::UnorderedListBlock(t) => &mut t.span,
+            LineToken::OrderedList(t) => &mut t.span,
+            LineToken::UnorderedList(t) => &mut t.span,
+            LineToken::NoteToken(t) => &mut t.span,
+            LineToken::HorizontalRule(span) => span,
+            LineToken::Table(t) => &mut t.span,
         }
     }
 
-    pub fn new_list_block(token: LineToken) -> LineToken {
+    /// Applies `f` to both ends of the span of this token and of every block
+    /// and inline token inside it.
+    pub fn map_spans(&mut self, f: &dyn Fn(Position) -> Position) {
+        self.span_mut().map(f);
+        let (children, inline_tokens) = match self {
+            LineToken::HeaderToken(t) => (None, Some(&mut t.inline_tokens)),
+            LineToken::Paragraph(t) => (None, Some(&mut t.inline_tokens)),
+            LineToken::Quote(t) => (Some(&mut t.children), None),
+            LineToken::OrderedListBlock(t) => (Some(&mut t.lists), None),
+            LineToken::UnorderedListBlock(t) => (Some(&mut t.lists), None),
+            LineToken::OrderedList(t) => (Some(&mut t.children), None),
+            LineToken::UnorderedList(t) => (Some(&mut t.children), None),
+            LineToken::Table(t) => {
+                let rows = std::iter::once(&mut t.header).chain(&mut t.rows);
+                for row in rows {
+                    row.span.map(f);
+                    for cell in &mut row.cells {
+                        cell.span.map(f);
+                        cell.inline_tokens.iter_mut().for_each(|t| t.map_spans(f));
+                    }
+                }
+                (None, None)
+            }
+            _ => (None, None),
+        };
+        children.into_iter().flatten().for_each(|t| t.map_spans(f));
+        inline_tokens
+            .into_iter()
+            .flatten()
+            .for_each(|t| t.map_spans(f));
+    }
+
+    /// Whether `line` is a horizontal rule: three or more `-`, `*` or `_`,
+    /// optionally separated by spaces or tabs. A rule wins over a list item
+    /// when a line could be both, so `- - -` and `* * *` are rules.
+    pub fn is_horizontal_rule(line: &str) -> bool {
+        let re = regex!(r"^ {0,3}((-[ \t]*){3,}|(\*[ \t]*){3,}|(_[ \t]*){3,})$");
+        re.is_match(line)
+    }
+
+    pub fn is_blank(line: &str) -> bool {
+        line.trim().is_empty()
+    }
+
+    pub fn new_list_block(token: LineToken) -> Result<LineToken, Error> {
         match token {
-            LineToken::UnorderedList(_) => {
-                LineToken::UnorderedListBlock(UnorderedListBlock::new(token))
+            LineToken::UnorderedList(_) => Ok(LineToken::UnorderedListBlock(
+                UnorderedListBlock::new(token)?,
+            )),
+            LineToken::OrderedList(_) => {
+                Ok(LineToken::OrderedListBlock(OrderedListBlock::new(token)?))
             }
-            LineToken::OrderedList(_) => LineToken::OrderedListBlock(OrderedListBlock::new(token)),
-            _ => panic!(),
+            _ => Err(Error::NotAListItem),
         }
     }
 
+    /// Returns the list item that `line` starts, if any. Its children are
+    /// tokenized as if at the deepest level, without containers. Use
+    /// `list_marker` when the children don't matter.
     pub fn is_list(line: &str) -> Option<LineToken> {
-        let re = Regex::new(r"^((?P<ordered>\d+[.)] )|(?P<unordered>[-*] ))(.+)").unwrap();
-        let caps = re.captures(line);
-        if let Some(mat) = caps {
-            let left_text = mat.get(mat.len() - 1).unwrap().as_str();
-            let inline_tokens = InlineToken::tokenizer(left_text);
-            if let Some(_) = mat.name("ordered") {
-                let mut chars = line.chars();
-                let order = chars.next().unwrap();
-                let symbol = chars.next().unwrap();
-                let token = OrderedList::new(order, symbol, inline_tokens);
-                Some(LineToken::OrderedList(token))
-            } else {
-                let symbol = line.chars().next().unwrap();
-                let token = UnorderedList::new(symbol, inline_tokens);
-                Some(LineToken::UnorderedList(token))
+        match LineToken::list_tokenizer(&[line], 0, MAX_DEPTH - 1, Extensions::default()) {
+            Ok(Some((token, _))) => Some(token),
+            _ => None,
+        }
+    }
+
+    /// Returns the width of the indentation of `line`, with tabs advancing to
+    /// the next multiple of 4 columns.
+    pub fn indent_width(line: &str) -> usize {
+        let mut width = 0;
+        for c in line.chars() {
+            match c {
+                ' ' => width += 1,
+                '\t' => width += 4 - width % 4,
+                _ => break,
+            }
+        }
+        width
+    }
+
+    /// Strips `width` columns of indentation from `line`. Returns `None` if the
+    /// line is indented less than that. A tab that only partly fits in `width`
+    /// is stripped whole.
+    pub fn strip_indent(line: &str, width: usize) -> Option<&str> {
+        let mut column = 0;
+        for (i, c) in line.char_indices() {
+            if column >= width {
+                return Some(&line[i..]);
+            }
+            match c {
+                ' ' => column += 1,
+                '\t' => column += 4 - column % 4,
+                _ => return None,
             }
+        }
+        if column >= width {
+            Some("")
         } else {
             None
         }
     }
 
-    pub fn is_prev_list(tokens: &Vec<LineToken>) -> char {
+    /// Tokenizes the list item starting at `lines[index]`.
+    ///
+    /// The marker may be indented by up to three spaces. The item owns every
+    /// following line indented at least as far as the text after its marker,
+    /// blank lines between them, and lazy paragraph continuation lines. Those
+    /// lines are tokenized again, with the indent stripped, to build the item's
+    /// children, so a list indented under an item becomes a child list of that
+    /// item. Returns the item and the index of its last line, or `None` if
+    /// there is no list item or it would be nested deeper than `MAX_DEPTH`.
+    pub fn list_tokenizer(
+        lines: &[&str],
+        index: usize,
+        depth: usize,
+        extensions: Extensions,
+    ) -> Result<Option<(LineToken, usize)>, Error> {
+        let line = lines[index];
+        let marker = match LineToken::list_marker(line) {
+            Some(marker) if depth < MAX_DEPTH => marker,
+            _ => return Ok(None),
+        };
+        let offset = marker.offset;
+        let first = &line[offset.min(line.len())..];
+        let (checked, first) = match LineToken::task_marker(first, extensions) {
+            Some((checked, rest)) => (Some(checked), rest),
+            None => (None, first),
+        };
+        let mut inner = vec![Cow::Borrowed(first)];
+        let mut last = index;
+        let mut i = index + 1;
+        while i < lines.len() {
+            let l = lines[i];
+            if LineToken::is_blank(l) {
+                inner.push(Cow::Borrowed(""));
+            } else if let Some(rest) = LineToken::strip_indent(l, offset) {
+                inner.push(Cow::Borrowed(rest));
+                last = i;
+            } else if i == last + 1
+                && Paragraph::is_paragraph_text(&inner[inner.len() - 1])
+                && Paragraph::is_continued_by(l)
+                && LineToken::list_marker(l).is_none()
+            {
+                inner.push(Paragraph::lazy_line(l));
+                last = i;
+            } else {
+                break;
+            }
+            i += 1;
+        }
+        inner.truncate(last - index + 1);
+        let inner: Vec<&str> = inner.iter().map(|l| l.as_ref()).collect();
+        let mut children = Tokenizer::tokenize_lines(&inner, depth + 1, extensions)?;
+        shift_spans(&mut children, lines, &inner, index);
+        let mut token = match marker.order {
+            Some(order) => LineToken::OrderedList(OrderedList {
+                checked,
+                ..OrderedList::new(order, marker.symbol, children)
+            }),
+            None => LineToken::UnorderedList(UnorderedList {
+                checked,
+                ..UnorderedList::new(marker.symbol, children)
+            }),
+        };
+        let indent = line.len() - line.trim_start().len();
+        *token.span_mut() =
+            Span::from_lines(index + 1, indent + 1, last + 1, lines[last].len() + 1);
+        Ok(Some((token, last)))
+    }
+
+    /// Reads the list item marker at the start of `line`, without tokenizing
+    /// the item's content.
+    pub fn list_marker(line: &str) -> Option<ListMarker> {
+        let re = regex!(r"^ {0,3}((?P<ordered>\d{1,9}[.)])|(?P<unordered>[-*])) (.+)");
+        let mat = re.captures(line)?;
+        let marker = mat.get(1)?;
+        let rest = &line[marker.end()..];
+        let spaces = rest.len() - rest.trim_start_matches(' ').len();
+        let is_empty = LineToken::is_blank(rest);
+        let offset = if spaces > 4 || is_empty {
+            marker.end() + 1
+        } else {
+            marker.end() + spaces
+        };
+        let (number, symbol) = marker.as_str().split_at(marker.as_str().len() - 1);
+        let order = mat.name("ordered").map(|_| number.parse().unwrap_or(1));
+        Some(ListMarker {
+            order,
+            symbol: symbol.chars().next().unwrap_or('-'),
+            offset,
+            is_empty,
+        })
+    }
+
+    /// Reads the `[ ]` or `[x]` at the start of `text`, the first line of a
+    /// list item after its marker, that makes the item a task, if `extensions`
+    /// has task lists. Returns whether the task is checked and the text after
+    /// the brackets, which can't be blank.
+    pub fn task_marker(text: &str, extensions: Extensions) -> Option<(bool, &str)> {
+        if !extensions.task_lists {
+            return None;
+        }
+        LineToken::try_task_marker(text)
+    }
+
+    #[cfg(feature = "task-lists")]
+    fn try_task_marker(text: &str) -> Option<(bool, &str)> {
+        let caps = regex!(r"^\[([ xX])\][ \t]+").captures(text)?;
+        let rest = &text[caps.get(0)?.end()..];
+        if LineToken::is_blank(rest) {
+            return None;
+        }
+        Some((&caps[1] != " ", rest))
+    }
+
+    #[cfg(not(feature = "task-lists"))]
+    fn try_task_marker(_text: &str) -> Option<(bool, &str)> {
+        None
+    }
+
+    pub fn is_prev_list(tokens: &[LineToken]) -> char {
         let last = &tokens.last();
         if let Some(LineToken::UnorderedListBlock(t)) = last {
             t.get_symbol()
@@ -71,143 +330,375 @@ impl LineToken {
         }
     }
 
-    pub fn same_list_block_as_prev(token: &LineToken, tokens: &Vec<LineToken>) -> bool {
+    pub fn same_list_block_as_prev(token: &LineToken, tokens: &[LineToken]) -> bool {
         let prev = LineToken::is_prev_list(tokens);
         if let LineToken::UnorderedList(t) = token {
             return t.symbol == prev;
         } else if let LineToken::OrderedList(t) = token {
             return prev == t.symbol;
         }
-        return false;
+        false
     }
 
-    pub fn push_to_last_list_block(tokens: &mut Vec<LineToken>, token: LineToken) {
-        let last = tokens.last_mut().unwrap();
-        match last {
-            LineToken::OrderedListBlock(t) => t.push(token),
-            LineToken::UnorderedListBlock(t) => t.push(token),
-            _ => panic!(),
+    pub fn push_to_last_list_block(
+        tokens: &mut [LineToken],
+        token: LineToken,
+    ) -> Result<(), Error> {
+        match tokens.last_mut() {
+            Some(LineToken::OrderedListBlock(t)) => t.push(token),
+            Some(LineToken::UnorderedListBlock(t)) => t.push(token),
+            _ => Err(Error::NotAListBlock),
+        }
+    }
+
+    /// Marks the list block at the end of `tokens` as loose, so its items
+    /// render their paragraphs inside `<p>`.
+    pub fn loosen_last_list_block(tokens: &mut [LineToken]) {
+        match tokens.last_mut() {
+            Some(LineToken::OrderedListBlock(t)) => t.tight = false,
+            Some(LineToken::UnorderedListBlock(t)) => t.tight = false,
+            _ => (),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HeaderToken {
     pub level: usize,
     pub inline_tokens: Vec<InlineToken>,
+    pub span: Span,
 }
 
 impl HeaderToken {
-    pub fn try_tokenize(line: &str) -> Option<LineToken> {
-        let re = Regex::new(r"^(#{1,6}) (.*)").unwrap();
+    pub fn is_header(line: &str) -> bool {
+        regex!(r"^(#{1,6}) (.*)").is_match(line)
+    }
+
+    pub fn try_tokenize(line: &str, extensions: Extensions) -> Option<LineToken> {
+        let re = regex!(r"^(#{1,6}) (.*)");
         let caps = re.captures(line);
         match caps {
             Some(v) => {
                 let level = v.get(1).unwrap().as_str().len();
-                let inner_text = v.get(2).unwrap().as_str();
+                let inner_text = v.get(2).unwrap();
+                let mut inline_tokens =
+                    InlineToken::tokenizer_with(inner_text.as_str(), extensions);
+                shift_inline_spans(&mut inline_tokens, 0, &[inner_text.start() as isize]);
                 let token = HeaderToken {
                     level,
-                    inline_tokens: InlineToken::tokenizer(inner_text),
+                    inline_tokens,
+                    span: Span::from_lines(1, 1, 1, line.len() + 1),
                 };
                 Some(LineToken::HeaderToken(token))
             }
             None => None,
         }
     }
+
+    /// Returns the level of the setext heading that `line` underlines, if it is
+    /// an underline: a run of `=` for level 1 or of `-` for level 2, indented
+    /// by at most three spaces.
+    pub fn setext_level(line: &str) -> Option<usize> {
+        let re = regex!(r"^ {0,3}(=+|-+)[ \t]*$");
+        let caps = re.captures(line)?;
+        if caps[1].starts_with('=') {
+            Some(1)
+        } else {
+            Some(2)
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Paragraph {
     pub inline_tokens: Vec<InlineToken>,
+    pub span: Span,
 }
 
 impl Paragraph {
-    pub fn tokenizer(line: &str) -> LineToken {
-        LineToken::Paragraph(Paragraph {
-            inline_tokens: InlineToken::tokenizer(line),
-        })
+    /// Whether `line` could be a line of paragraph text, so that a lazy
+    /// continuation line after it belongs to the same paragraph.
+    pub fn is_paragraph_text(line: &str) -> bool {
+        !LineToken::is_blank(line)
+            && LineToken::indent_width(line) < 4
+            && !HeaderToken::is_header(line)
+            && CodeBlock::tokenizer(&[line], 0).is_none()
+            && !LineToken::is_horizontal_rule(line)
+    }
+
+    /// Whether `line` starts a block that ends a paragraph before it: a
+    /// header, a code fence, a quote, a horizontal rule or a non-empty list
+    /// item. An ordered list only interrupts a paragraph when it starts at 1.
+    pub fn is_interrupted_by(line: &str) -> bool {
+        let starts_list = match LineToken::list_marker(line) {
+            Some(marker) => !marker.is_empty && marker.order.unwrap_or(1) == 1,
+            None => false,
+        };
+        starts_list
+            || HeaderToken::is_header(line)
+            || CodeBlock::tokenizer(&[line], 0).is_some()
+            || line.starts_with('>')
+            || LineToken::is_horizontal_rule(line)
+    }
+
+    /// Whether `line` continues a paragraph before it.
+    pub fn is_continued_by(line: &str) -> bool {
+        !LineToken::is_blank(line) && !Paragraph::is_interrupted_by(line)
+    }
+
+    /// Prepares a lazy continuation line to be tokenized inside a container. A
+    /// lazy line can't be a setext underline, so an `===` underline is indented
+    /// past the point where it counts as one. Paragraph lines are trimmed, so
+    /// the text stays the same.
+    pub fn lazy_line(line: &str) -> Cow<'_, str> {
+        if HeaderToken::setext_level(line).is_some() {
+            Cow::Owned(format!("    {}", line))
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    /// Tokenizes the paragraph starting at `lines[index]`.
+    ///
+    /// Following lines belong to the paragraph until a blank line or a line
+    /// that interrupts it, including the header row of a table if `extensions`
+    /// has tables. The lines are joined with newlines, which the inline
+    /// tokenizer turns into soft or hard breaks. If the lines are followed by a
+    /// setext underline they become a header instead, ending on the underline.
+    /// Returns the token and the index of its last line.
+    pub fn tokenizer(
+        lines: &[&str],
+        mut index: usize,
+        extensions: Extensions,
+    ) -> (LineToken, usize) {
+        let first = index;
+        let indent = |line: &str| (line.len() - line.trim_start().len()) as isize;
+        let mut shifts = vec![indent(lines[index])];
+        let mut text = String::from(lines[index].trim_start());
+        while index + 1 < lines.len() {
+            let next = lines[index + 1];
+            if let Some(level) = HeaderToken::setext_level(next) {
+                let mut inline_tokens = InlineToken::tokenizer_with(text.trim_end(), extensions);
+                shift_inline_spans(&mut inline_tokens, first, &shifts);
+                let token = HeaderToken {
+                    level,
+                    inline_tokens,
+                    span: Span::from_lines(
+                        first + 1,
+                        shifts[0] as usize + 1,
+                        index + 2,
+                        next.trim_end().len() + 1,
+                    ),
+                };
+                return (LineToken::HeaderToken(token), index + 1);
+            }
+            if !Paragraph::is_continued_by(next) || Table::starts_at(lines, index + 1, extensions) {
+                break;
+            }
+            index += 1;
+            shifts.push(indent(next));
+            text.push('\n');
+            text.push_str(next.trim_start());
+        }
+        let mut inline_tokens = InlineToken::tokenizer_with(text.trim_end(), extensions);
+        shift_inline_spans(&mut inline_tokens, first, &shifts);
+        let span = Span::from_lines(
+            first + 1,
+            shifts[0] as usize + 1,
+            index + 1,
+            lines[index].trim_end().len() + 1,
+        );
+        let token = Paragraph {
+            inline_tokens,
+            span,
+        };
+        (LineToken::Paragraph(token), index)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CodeBlock {
     pub text: String,
+    pub info: Option<String>,
+    pub span: Span,
 }
 
 impl CodeBlock {
     pub fn new(text: String) -> Self {
-        Self { text }
+        Self {
+            text,
+            info: None,
+            span: Span::default(),
+        }
+    }
+
+    /// The first word of the info string, which names the language of the
+    /// code.
+    pub fn language(&self) -> Option<&str> {
+        self.info
+            .as_ref()
+            .and_then(|info| info.split_whitespace().next())
     }
 
-    pub fn tokenizer(lines: &Vec<&str>, mut index: usize) -> (LineToken, usize) {
-        index = index + 1;
+    /// Tokenizes the fenced code block opened by `lines[index]`, if that line
+    /// is an opening fence of three or more backticks or tildes.
+    ///
+    /// The block ends at a fence of the same character that is at least as
+    /// long as the opening one, or at the last line. The indentation of the
+    /// opening fence is removed from each line of content. Returns the block and
+    /// the index of its closing fence.
+    pub fn tokenizer(lines: &[&str], mut index: usize) -> Option<(LineToken, usize)> {
+        let open = regex!(r"^( {0,3})(`{3,}|~{3,})(.*)$");
+        let close = regex!(r"^ {0,3}(`{3,}|~{3,})[ \t]*$");
+        let mat = open.captures(lines[index])?;
+        let indent = mat.get(1).unwrap().as_str().len();
+        let fence = mat.get(2).unwrap().as_str();
+        let info = mat.get(3).unwrap().as_str().trim();
+        if fence.starts_with('`') && info.contains('`') {
+            return None;
+        }
+        let first = index;
+        index += 1;
         let mut block: Vec<&str> = Vec::new();
-        while index < lines.len() && lines[index] != "```" {
-            block.push(lines[index]);
+        while index < lines.len() {
+            let line = lines[index];
+            if let Some(closing) = close.captures(line) {
+                let closing = closing.get(1).unwrap().as_str();
+                if closing.starts_with(&fence[..1]) && closing.len() >= fence.len() {
+                    break;
+                }
+            }
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            block.push(&line[spaces.min(indent)..]);
             index += 1;
         }
-        let text = block.join("\n");
-        let block = CodeBlock::new(text);
-        let token = LineToken::CodeBlock(block);
-        (token, index)
+        let last = index.min(lines.len() - 1);
+        let block = CodeBlock {
+            text: block.join("\n"),
+            info: if info.is_empty() {
+                None
+            } else {
+                Some(info.to_string())
+            },
+            span: Span::from_lines(first + 1, indent + 1, last + 1, lines[last].len() + 1),
+        };
+        Some((LineToken::CodeBlock(block), index))
+    }
+
+    /// Tokenizes the indented code block starting at `lines[index]`, if that
+    /// line is indented by four or more columns.
+    ///
+    /// The block takes every following line that is indented as far, plus the
+    /// blank lines between them, and removes four columns of indentation from
+    /// each. Returns the block and the index of its last line.
+    pub fn indented_tokenizer(lines: &[&str], index: usize) -> Option<(LineToken, usize)> {
+        if LineToken::is_blank(lines[index]) || LineToken::indent_width(lines[index]) < 4 {
+            return None;
+        }
+        let mut last = index;
+        let mut i = index + 1;
+        while i < lines.len() {
+            if LineToken::indent_width(lines[i]) >= 4 {
+                last = i;
+            } else if !LineToken::is_blank(lines[i]) {
+                break;
+            }
+            i += 1;
+        }
+        let block: Vec<&str> = lines[index..=last]
+            .iter()
+            .map(|line| LineToken::strip_indent(line, 4).unwrap_or(""))
+            .collect();
+        let mut block = CodeBlock::new(block.join("\n"));
+        block.span = Span::from_lines(index + 1, 1, last + 1, lines[last].len() + 1);
+        Some((LineToken::CodeBlock(block), last))
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Quote {
-    pub inline_tokens: Vec<InlineToken>,
+    pub children: Vec<LineToken>,
+    pub span: Span,
 }
 
 impl Quote {
-    pub fn tokenizer(lines: &Vec<&str>, mut index: usize) -> (LineToken, usize) {
-        let mut temp = vec![&lines[index][1..]];
-        index += 1;
-        while index < lines.len() && lines[index].ends_with("  ") {
-            temp.push(lines[index]);
+    /// Tokenizes the block quote starting at `lines[index]`.
+    ///
+    /// The quote takes every following line that starts with `>`, plus lazy
+    /// continuation lines of a paragraph inside it. The `>` markers are
+    /// stripped and the remaining lines are tokenized as the quote's children,
+    /// one level deeper than `depth`.
+    pub fn tokenizer(
+        lines: &[&str],
+        mut index: usize,
+        depth: usize,
+        extensions: Extensions,
+    ) -> Result<(LineToken, usize), Error> {
+        let first = index;
+        let mut inner: Vec<Cow<str>> = Vec::new();
+        while index < lines.len() {
+            let line = lines[index];
+            if let Some(rest) = line.strip_prefix('>') {
+                inner.push(Cow::Borrowed(rest.strip_prefix(' ').unwrap_or(rest)));
+            } else if inner
+                .last()
+                .is_some_and(|l| Paragraph::is_paragraph_text(l))
+                && Paragraph::is_continued_by(line)
+            {
+                inner.push(Paragraph::lazy_line(line));
+            } else {
+                break;
+            }
             index += 1;
         }
-        if index < lines.len() && lines[index - 1].ends_with("  ") {
-            temp.push(lines[index]);
-        } else {
-            index -= 1;
+        let inner: Vec<&str> = inner.iter().map(|l| l.as_ref()).collect();
+        let mut children = Tokenizer::tokenize_lines(&inner, depth + 1, extensions)?;
+        shift_spans(&mut children, lines, &inner, first);
+        let last = index.saturating_sub(1);
+        let token = Quote {
+            children,
+            span: Span::from_lines(first + 1, 1, last + 1, lines[last].len() + 1),
         };
-        let mut inline_tokens: Vec<InlineToken> = Vec::new();
-        for l in temp {
-            inline_tokens.append(&mut InlineToken::tokenizer(l));
-            inline_tokens.push(InlineToken::BreakToken);
-        }
-        inline_tokens.pop();
-        let token = Quote { inline_tokens };
-        (LineToken::Quote(token), index)
+        Ok((LineToken::Quote(token), last))
     }
 }
 
-#[derive(Debug)]
+/// A run of ordered list items sharing the same delimiter. Lists nested by
+/// indentation are held as children of the item they are indented under.
+#[derive(Debug, Clone)]
 pub struct OrderedListBlock {
-    pub start: char,
+    pub start: usize,
     pub symbol: char,
+    pub tight: bool,
     pub lists: Vec<LineToken>,
+    pub span: Span,
 }
 
 impl OrderedListBlock {
-    pub fn new(token: LineToken) -> Self {
+    pub fn new(token: LineToken) -> Result<Self, Error> {
         if let LineToken::OrderedList(ref t) = token {
             let symbol = t.symbol;
             let start = t.order;
-            Self {
+            Ok(Self {
                 start,
                 symbol,
+                tight: true,
+                span: t.span,
                 lists: vec![token],
-            }
+            })
         } else {
-            panic!()
+            Err(Error::NotAListItem)
         }
     }
 
-    pub fn push(&mut self, token: LineToken) {
+    pub fn push(&mut self, token: LineToken) -> Result<(), Error> {
         match token {
-            LineToken::OrderedList(_) => self.lists.push(token),
-            _ => panic!(),
+            LineToken::OrderedList(ref t) => {
+                self.span.end = t.span.end;
+                self.lists.push(token);
+            }
+            _ => return Err(Error::NotAListItem),
         }
+        Ok(())
     }
 
     pub fn get_symbol(&self) -> char {
@@ -215,22 +706,28 @@ impl OrderedListBlock {
     }
 }
 
-#[derive(Debug)]
+/// A run of unordered list items sharing the same bullet. Lists nested by
+/// indentation are held as children of the item they are indented under.
+#[derive(Debug, Clone)]
 pub struct UnorderedListBlock {
     pub symbol: char,
+    pub tight: bool,
     pub lists: Vec<LineToken>,
+    pub span: Span,
 }
 
 impl UnorderedListBlock {
-    pub fn new(token: LineToken) -> Self {
+    pub fn new(token: LineToken) -> Result<Self, Error> {
         if let LineToken::UnorderedList(ref t) = token {
             let symbol = t.symbol;
-            Self {
+            Ok(Self {
                 symbol,
+                tight: true,
+                span: t.span,
                 lists: vec![token],
-            }
+            })
         } else {
-            panic!()
+            Err(Error::NotAListItem)
         }
     }
 
@@ -238,68 +735,307 @@ impl UnorderedListBlock {
         self.symbol
     }
 
-    pub fn push(&mut self, token: LineToken) {
+    pub fn push(&mut self, token: LineToken) -> Result<(), Error> {
         match token {
-            LineToken::UnorderedList(_) => self.lists.push(token),
-            _ => panic!(),
+            LineToken::UnorderedList(ref t) => {
+                self.span.end = t.span.end;
+                self.lists.push(token);
+            }
+            _ => return Err(Error::NotAListItem),
         }
+        Ok(())
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrderedList {
-    pub order: char,
+    pub order: usize,
     pub symbol: char,
-    pub inline_tokens: Vec<InlineToken>,
+    /// Whether the item is a checked task, `None` if it isn't a task.
+    pub checked: Option<bool>,
+    pub children: Vec<LineToken>,
+    pub span: Span,
 }
 
 impl OrderedList {
-    pub fn new(order: char, symbol: char, inline_tokens: Vec<InlineToken>) -> Self {
+    pub fn new(order: usize, symbol: char, children: Vec<LineToken>) -> Self {
         Self {
             order,
             symbol,
-            inline_tokens,
+            checked: None,
+            children,
+            span: Span::default(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct UnorderedList {
-    pub inline_tokens: Vec<InlineToken>,
+    pub children: Vec<LineToken>,
     pub symbol: char,
+    /// Whether the item is a checked task, `None` if it isn't a task.
+    pub checked: Option<bool>,
+    pub span: Span,
 }
 
 impl UnorderedList {
-    pub fn new(symbol: char, inline_tokens: Vec<InlineToken>) -> Self {
+    pub fn new(symbol: char, children: Vec<LineToken>) -> Self {
         Self {
             symbol,
-            inline_tokens,
+            checked: None,
+            children,
+            span: Span::default(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct NoteToken {
     pub name: String,
     pub link: String,
+    pub span: Span,
 }
 
 impl NoteToken {
     pub fn new(name: String, link: String) -> Self {
-        Self { name, link }
+        Self {
+            name,
+            link,
+            span: Span::default(),
+        }
     }
     pub fn try_tokenize(line: &str) -> Option<LineToken> {
-        let re = Regex::new(r"\[(.*)\]:(.*)").unwrap();
+        let re = regex!(r"^ {0,3}\[(.*)\]:(.*)");
         if let Some(mat) = re.captures(line) {
             let name = String::from(mat.get(1).unwrap().as_str());
             let link = String::from(mat.get(2).unwrap().as_str());
-            Some(LineToken::NoteToken(NoteToken::new(name, link)))
+            let mut token = NoteToken::new(name, link);
+            token.span = Span::from_lines(1, 1, 1, line.len() + 1);
+            Some(LineToken::NoteToken(token))
         } else {
             None
         }
     }
 }
 
+/// How the text of a table column is aligned, given by the colons of its
+/// delimiter row: `:--` for left, `:-:` for center and `--:` for right.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Alignment {
+    /// No colons: left to the renderer.
+    #[default]
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+/// A GFM table: a header row, a delimiter row with the alignment of every
+/// column, and the body rows up to a blank line or another block. Every row has
+/// one cell per column.
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub header: TableRow,
+    pub rows: Vec<TableRow>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableRow {
+    pub cells: Vec<TableCell>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableCell {
+    pub inline_tokens: Vec<InlineToken>,
+    pub span: Span,
+}
+
+impl Table {
+    /// Whether a table with its header row at `lines[index]` starts there.
+    /// Always false unless `extensions` has tables and the crate was built
+    /// with the `tables` feature.
+    pub fn starts_at(lines: &[&str], index: usize, extensions: Extensions) -> bool {
+        extensions.tables && Table::alignments(lines, index).is_some()
+    }
+
+    /// Tokenizes the table whose header row is `lines[index]`, if `extensions`
+    /// has tables. Returns the table and the index of its last row.
+    pub fn tokenizer(
+        lines: &[&str],
+        index: usize,
+        extensions: Extensions,
+    ) -> Option<(LineToken, usize)> {
+        if !extensions.tables {
+            return None;
+        }
+        let alignments = Table::alignments(lines, index)?;
+        let header = TableRow::tokenizer(lines, index, alignments.len(), extensions);
+        let mut rows = Vec::new();
+        let mut last = index + 1;
+        while last + 1 < lines.len() && Paragraph::is_continued_by(lines[last + 1]) {
+            last += 1;
+            rows.push(TableRow::tokenizer(
+                lines,
+                last,
+                alignments.len(),
+                extensions,
+            ));
+        }
+        let indent = lines[index].len() - lines[index].trim_start().len();
+        let table = Table {
+            alignments,
+            header,
+            rows,
+            span: Span::from_lines(index + 1, indent + 1, last + 1, lines[last].len() + 1),
+        };
+        Some((LineToken::Table(table), last))
+    }
+
+    #[cfg(not(feature = "tables"))]
+    fn alignments(_lines: &[&str], _index: usize) -> Option<Vec<Alignment>> {
+        None
+    }
+
+    /// Reads the delimiter row under the header row `lines[index]`: a cell of
+    /// dashes, with optional colons at either end, for every cell of the
+    /// header. It needs a pipe, so that a setext underline isn't taken for one.
+    #[cfg(feature = "tables")]
+    fn alignments(lines: &[&str], index: usize) -> Option<Vec<Alignment>> {
+        let header = lines[index];
+        let delimiter = *lines.get(index + 1)?;
+        if !delimiter.contains('|')
+            || LineToken::indent_width(header) >= 4
+            || LineToken::indent_width(delimiter) >= 4
+        {
+            return None;
+        }
+        let re = regex!(r"^(:?)-+(:?)$");
+        let alignments = TableRow::split(delimiter)
+            .into_iter()
+            .map(|(start, end)| {
+                let caps = re.captures(&delimiter[start..end])?;
+                Some(match (caps[1].is_empty(), caps[2].is_empty()) {
+                    (true, true) => Alignment::None,
+                    (false, true) => Alignment::Left,
+                    (false, false) => Alignment::Center,
+                    (true, false) => Alignment::Right,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if alignments.is_empty() || TableRow::split(header).len() != alignments.len() {
+            return None;
+        }
+        Some(alignments)
+    }
+}
+
+impl TableRow {
+    /// Tokenizes the row `lines[index]` into `columns` cells. Missing cells
+    /// are empty and extra ones are dropped.
+    pub fn tokenizer(
+        lines: &[&str],
+        index: usize,
+        columns: usize,
+        extensions: Extensions,
+    ) -> TableRow {
+        let line = lines[index];
+        let mut cells: Vec<TableCell> = TableRow::split(line)
+            .into_iter()
+            .take(columns)
+            .map(|(start, end)| TableCell::tokenizer(line, index, start, end, extensions))
+            .collect();
+        let end = line.trim_end().len();
+        while cells.len() < columns {
+            cells.push(TableCell {
+                inline_tokens: Vec::new(),
+                span: Span::from_lines(index + 1, end + 1, index + 1, end + 1),
+            });
+        }
+        let indent = line.len() - line.trim_start().len();
+        TableRow {
+            cells,
+            span: Span::from_lines(index + 1, indent + 1, index + 1, line.len() + 1),
+        }
+    }
+
+    /// Splits a row at its pipes, and returns the byte range of the text of
+    /// every cell, trimmed. The pipes at the start and the end of the row are
+    /// optional. Pipes escaped with a backslash or inside a code span don't
+    /// split cells.
+    pub fn split(line: &str) -> Vec<(usize, usize)> {
+        let bytes = line.as_bytes();
+        let end = line.trim_end().len();
+        let mut start = line.len() - line.trim_start().len();
+        if bytes.get(start) == Some(&b'|') {
+            start += 1;
+        }
+        let mut result = Vec::new();
+        let mut i = start;
+        while i < end {
+            match bytes[i] {
+                b'\\' => i += 2,
+                b'`' => i += SpecialToken::try_tokenize_code(&line[i..end]).1,
+                b'|' => {
+                    result.push((start, i));
+                    start = i + 1;
+                    i += 1;
+                }
+                _ => i += 1,
+            }
+        }
+        if start < end || result.is_empty() {
+            result.push((start, end.max(start)));
+        }
+        result
+            .into_iter()
+            .map(|(start, end)| {
+                let cell = &line[start..end];
+                let start = start + cell.len() - cell.trim_start().len();
+                (start, start + cell.trim().len())
+            })
+            .collect()
+    }
+}
+
+impl TableCell {
+    /// Tokenizes the cell text `line[start..end]` of the row `lines[index]`,
+    /// with its escaped pipes unescaped, even in code spans.
+    pub fn tokenizer(
+        line: &str,
+        index: usize,
+        start: usize,
+        end: usize,
+        extensions: Extensions,
+    ) -> TableCell {
+        let text = &line[start..end];
+        // Offsets in the unescaped text of the pipes that had a backslash.
+        let escapes: Vec<usize> = text
+            .match_indices("\\|")
+            .enumerate()
+            .map(|(n, (i, _))| i - n)
+            .collect();
+        let mut inline_tokens = InlineToken::tokenizer_with(&text.replace("\\|", "|"), extensions);
+        for token in &mut inline_tokens {
+            token.map_spans(&|p| {
+                let shift = escapes.iter().filter(|&&e| e < p.offset).count();
+                Position {
+                    line: index + 1,
+                    column: start + p.column + shift,
+                    ..p
+                }
+            });
+        }
+        TableCell {
+            inline_tokens,
+            span: Span::from_lines(index + 1, start + 1, index + 1, end + 1),
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::super::inline_token::tests::{assert_special_token_group, assert_text_token};
@@ -320,7 +1056,7 @@ pub mod tests {
             assert_eq!(token.lists.len(), texts.len());
             for (i, line) in token.lists.iter().enumerate() {
                 if let LineToken::UnorderedList(t) = line {
-                    assert_text_token(&t.inline_tokens[0], texts[i]);
+                    assert_text_token(&paragraph_tokens(&t.children)[0], texts[i]);
                 } else {
                     panic!();
                 }
@@ -330,14 +1066,14 @@ pub mod tests {
         }
     }
 
-    pub fn assert_ordered_lists(tokens: &LineToken, texts: Vec<&str>, start: char, symbol: char) {
+    pub fn assert_ordered_lists(tokens: &LineToken, texts: Vec<&str>, start: usize, symbol: char) {
         if let LineToken::OrderedListBlock(token) = tokens {
             assert_eq!(token.start, start);
             assert_eq!(token.symbol, symbol);
             assert_eq!(token.lists.len(), texts.len());
             for (i, line) in token.lists.iter().enumerate() {
                 if let LineToken::OrderedList(t) = line {
-                    assert_text_token(&t.inline_tokens[0], texts[i]);
+                    assert_text_token(&paragraph_tokens(&t.children)[0], texts[i]);
                 } else {
                     panic!();
                 }
@@ -347,6 +1083,14 @@ pub mod tests {
         }
     }
 
+    pub fn paragraph_tokens(children: &[LineToken]) -> &Vec<InlineToken> {
+        if let Some(LineToken::Paragraph(token)) = children.first() {
+            &token.inline_tokens
+        } else {
+            panic!("expected a paragraph, got {:?}", children);
+        }
+    }
+
     pub fn assert_paragraph_with_single_text(token: &LineToken, text: &str) {
         if let LineToken::Paragraph(token) = token {
             let tokens = &token.inline_tokens;
@@ -420,19 +1164,24 @@ pub mod tests {
     #[test]
     fn test_is_prev_list() {
         let tokens = vec![LineToken::OrderedListBlock(OrderedListBlock {
-            start: '1',
+            start: 1,
             symbol: ')',
+            tight: true,
             lists: vec![],
+            span: Span::default(),
         })];
         assert_eq!(LineToken::is_prev_list(&tokens), ')');
         let unordered_list = LineToken::UnorderedList(UnorderedList::new('*', vec![]));
         let tokens = vec![LineToken::UnorderedListBlock(UnorderedListBlock {
             symbol: '*',
+            tight: true,
             lists: vec![unordered_list],
+            span: Span::default(),
         })];
         assert_eq!(LineToken::is_prev_list(&tokens), '*');
         let tokens = vec![LineToken::Paragraph(Paragraph {
             inline_tokens: vec![],
+            span: Span::default(),
         })];
         assert_eq!(LineToken::is_prev_list(&tokens), NOT_LIST);
         let tokens = vec![];
@@ -441,32 +1190,26 @@ pub mod tests {
 
     #[test]
     fn test_is_prev_same_block_unordered_list() {
-        let ordered_list = LineToken::OrderedList(OrderedList::new('1', '.', vec![]));
-        let block = &vec![LineToken::OrderedListBlock(OrderedListBlock::new(
-            ordered_list,
-        ))];
-        let ordered_list = LineToken::OrderedList(OrderedList::new('2', '.', vec![]));
+        let ordered_list = LineToken::OrderedList(OrderedList::new(1, '.', vec![]));
+        let block = &[LineToken::OrderedListBlock(
+            OrderedListBlock::new(ordered_list).unwrap(),
+        )];
+        let ordered_list = LineToken::OrderedList(OrderedList::new(2, '.', vec![]));
         assert!(LineToken::same_list_block_as_prev(&ordered_list, block));
-        let ordered_list = LineToken::OrderedList(OrderedList::new('3', ')', vec![]));
-        assert_eq!(
-            false,
-            LineToken::same_list_block_as_prev(&ordered_list, block)
-        );
+        let ordered_list = LineToken::OrderedList(OrderedList::new(3, ')', vec![]));
+        assert!(!LineToken::same_list_block_as_prev(&ordered_list, block));
     }
 
     #[test]
     fn test_is_prev_same_block_ordered_list() {
         let unordered_list = LineToken::UnorderedList(UnorderedList::new('*', vec![]));
-        let block = &vec![LineToken::UnorderedListBlock(UnorderedListBlock::new(
-            unordered_list,
-        ))];
+        let block = &[LineToken::UnorderedListBlock(
+            UnorderedListBlock::new(unordered_list).unwrap(),
+        )];
         let unordered_list = LineToken::UnorderedList(UnorderedList::new('*', vec![]));
         assert!(LineToken::same_list_block_as_prev(&unordered_list, block));
         let unordered_list = LineToken::UnorderedList(UnorderedList::new('-', vec![]));
-        assert_eq!(
-            false,
-            LineToken::same_list_block_as_prev(&unordered_list, block)
-        );
+        assert!(!LineToken::same_list_block_as_prev(&unordered_list, block));
     }
 
     #[test]
@@ -482,7 +1225,7 @@ pub mod tests {
             }
             _ => panic!(),
         };
-        assert_eq!(level, 2 as usize);
+        assert_eq!(level, 2);
         match &inline_token[0] {
             InlineToken::TextToken(t) => assert_eq!(t.text, "Test"),
             _ => panic!(),
@@ -529,14 +1272,10 @@ pub mod tests {
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
         if let LineToken::Quote(token) = &result[0] {
-            let inline_tokens = &token.inline_tokens;
+            let inline_tokens = paragraph_tokens(&token.children);
             assert_eq!(inline_tokens.len(), 3);
-            assert_text_token(&inline_tokens[0], "this is  ");
-            if let InlineToken::BreakToken = &inline_tokens[1] {
-                assert!(true);
-            } else {
-                panic!();
-            }
+            assert_text_token(&inline_tokens[0], "this is");
+            assert!(matches!(inline_tokens[1], InlineToken::BreakToken(_)));
             assert_text_token(&inline_tokens[2], "a quote");
         } else {
             panic!();
@@ -549,7 +1288,7 @@ pub mod tests {
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
         if let LineToken::Quote(token) = &result[0] {
-            let inline_tokens = &token.inline_tokens;
+            let inline_tokens = paragraph_tokens(&token.children);
             assert_eq!(inline_tokens.len(), 1);
             assert_text_token(&inline_tokens[0], "this is");
         } else {
@@ -563,7 +1302,7 @@ pub mod tests {
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
         if let LineToken::Quote(token) = &result[0] {
-            let inline_tokens = &token.inline_tokens;
+            let inline_tokens = paragraph_tokens(&token.children);
             assert_eq!(inline_tokens.len(), 2);
             assert_text_token(&inline_tokens[0], "this is");
             assert_special_token_group(&inline_tokens[1], "a bold", '*');
@@ -574,12 +1313,15 @@ pub mod tests {
 
     #[test]
     fn test_single_line_quote_with_lines_surrounding() {
-        let text = "first paragraph\n>a quote\nsecond paragraph";
+        let text = "first paragraph\n>a quote\n\nsecond paragraph";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 3);
         if let LineToken::Quote(token) = &result[1] {
-            assert_eq!(token.inline_tokens.len(), 1);
-            assert_text_token(&token.inline_tokens[0], "a quote");
+            let inline_tokens = paragraph_tokens(&token.children);
+            assert_eq!(inline_tokens.len(), 1);
+            assert_text_token(&inline_tokens[0], "a quote");
+        } else {
+            panic!();
         }
         assert_paragraph_with_single_text(&result[0], "first paragraph");
         assert_paragraph_with_single_text(&result[2], "second paragraph");
@@ -587,18 +1329,17 @@ pub mod tests {
 
     #[test]
     fn test_multiple_lines_quote_with_lines_surrounding() {
-        let text = "first paragraph\n>a quote  \nanother quote\nsecond paragraph";
+        let text = "first paragraph\n>a quote  \nanother quote\n\nsecond paragraph";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 3);
         if let LineToken::Quote(token) = &result[1] {
-            assert_eq!(token.inline_tokens.len(), 3);
-            assert_text_token(&token.inline_tokens[0], "a quote  ");
-            assert_text_token(&token.inline_tokens[2], "another quote");
-            if let InlineToken::BreakToken = &token.inline_tokens[1] {
-                assert!(true);
-            } else {
-                panic!();
-            }
+            let inline_tokens = paragraph_tokens(&token.children);
+            assert_eq!(inline_tokens.len(), 3);
+            assert_text_token(&inline_tokens[0], "a quote");
+            assert_text_token(&inline_tokens[2], "another quote");
+            assert!(matches!(inline_tokens[1], InlineToken::BreakToken(_)));
+        } else {
+            panic!();
         }
         assert_paragraph_with_single_text(&result[0], "first paragraph");
         assert_paragraph_with_single_text(&result[2], "second paragraph");
@@ -610,7 +1351,7 @@ pub mod tests {
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
         if let LineToken::Quote(token) = &result[0] {
-            assert_eq!(token.inline_tokens.len(), 0);
+            assert_eq!(token.children.len(), 0);
         } else {
             panic!();
         }
@@ -621,7 +1362,7 @@ pub mod tests {
         let text = "1. this\n2. is\n3. a\n4. test";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
-        assert_ordered_lists(&result[0], vec!["this", "is", "a", "test"], '1', '.');
+        assert_ordered_lists(&result[0], vec!["this", "is", "a", "test"], 1, '.');
     }
 
     #[test]
@@ -629,7 +1370,7 @@ pub mod tests {
         let text = "1) this\n2) is\n3) a\n4) test";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
-        assert_ordered_lists(&result[0], vec!["this", "is", "a", "test"], '1', ')');
+        assert_ordered_lists(&result[0], vec!["this", "is", "a", "test"], 1, ')');
     }
 
     #[test]
@@ -637,7 +1378,7 @@ pub mod tests {
         let text = "2) this\n3) is\n4) a\n5) test";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 1);
-        assert_ordered_lists(&result[0], vec!["this", "is", "a", "test"], '2', ')');
+        assert_ordered_lists(&result[0], vec!["this", "is", "a", "test"], 2, ')');
     }
 
     #[test]
@@ -659,7 +1400,7 @@ pub mod tests {
 
     #[test]
     fn test_unordered_list_with_lines_surrounded() {
-        let text = "a simple line\n- a list\n- another\ntest";
+        let text = "a simple line\n- a list\n- another\n\ntest";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 3);
         assert_paragraph_with_single_text(&result[0], "a simple line");
@@ -669,12 +1410,12 @@ pub mod tests {
 
     #[test]
     fn test_ordered_list_with_lines_surrounded() {
-        let text = "a simple line\n1. a list\n2. another\ntest";
+        let text = "a simple line\n1. a list\n2. another\n\ntest";
         let result = Tokenizer::tokenizer(text);
         assert_eq!(result.len(), 3);
         assert_paragraph_with_single_text(&result[0], "a simple line");
         assert_paragraph_with_single_text(&result[2], "test");
-        assert_ordered_lists(&result[1], vec!["a list", "another"], '1', '.');
+        assert_ordered_lists(&result[1], vec!["a list", "another"], 1, '.');
     }
 
     #[test]
@@ -689,6 +1430,15 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn test_note_token_starts_the_line() {
+        assert!(NoteToken::try_tokenize("   [1]:http://a.com").is_some());
+        assert!(NoteToken::try_tokenize("see [x]: y and more").is_none());
+        let result = Tokenizer::tokenizer("see [x]: y and more");
+        assert_eq!(result.len(), 1);
+        assert!(matches!(result[0], LineToken::Paragraph(_)));
+    }
+
     #[test]
     fn test_note_token_in_tokenizer() {
         let text = "[1]:http://a.com";
@@ -700,16 +1450,696 @@ pub mod tests {
     #[test]
     fn test_is_horizontal_rule() {
         let line = "--";
-        assert_eq!(LineToken::is_horizontal_rule(line), false);
+        assert!(!LineToken::is_horizontal_rule(line));
         let line = "---";
-        assert_eq!(LineToken::is_horizontal_rule(line), true);
+        assert!(LineToken::is_horizontal_rule(line));
         let line = "-------";
-        assert_eq!(LineToken::is_horizontal_rule(line), true);
+        assert!(LineToken::is_horizontal_rule(line));
         let line = "**";
-        assert_eq!(LineToken::is_horizontal_rule(line), false);
+        assert!(!LineToken::is_horizontal_rule(line));
         let line = "***";
-        assert_eq!(LineToken::is_horizontal_rule(line), true);
+        assert!(LineToken::is_horizontal_rule(line));
         let line = "*******";
-        assert_eq!(LineToken::is_horizontal_rule(line), true);
+        assert!(LineToken::is_horizontal_rule(line));
+        let line = " - - -";
+        assert!(LineToken::is_horizontal_rule(line));
+        let line = "___\t";
+        assert!(LineToken::is_horizontal_rule(line));
+        let line = "--*";
+        assert!(!LineToken::is_horizontal_rule(line));
+        let line = "    ***";
+        assert!(!LineToken::is_horizontal_rule(line));
+    }
+
+    #[test]
+    fn test_setext_level() {
+        assert_eq!(HeaderToken::setext_level("==="), Some(1));
+        assert_eq!(HeaderToken::setext_level("   -  "), Some(2));
+        assert_eq!(HeaderToken::setext_level("= ="), None);
+        assert_eq!(HeaderToken::setext_level("    ==="), None);
+        assert_eq!(HeaderToken::setext_level("-=-"), None);
+    }
+
+    #[test]
+    fn test_setext_header() {
+        let result = Tokenizer::tokenizer("Title\n===\n\nSub *title*\nsecond line\n---");
+        assert_eq!(result.len(), 2);
+        if let LineToken::HeaderToken(token) = &result[0] {
+            assert_eq!(token.level, 1);
+            assert_eq!(token.inline_tokens.len(), 1);
+            assert_text_token(&token.inline_tokens[0], "Title");
+        } else {
+            panic!();
+        }
+        if let LineToken::HeaderToken(token) = &result[1] {
+            assert_eq!(token.level, 2);
+            assert_eq!(token.inline_tokens.len(), 4);
+            assert!(matches!(&token.inline_tokens[2], InlineToken::SoftBreak(_)));
+            assert_text_token(&token.inline_tokens[3], "second line");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_setext_underline_without_paragraph() {
+        let result = Tokenizer::tokenizer("===\n\n---\n\n- foo\n---");
+        assert_eq!(result.len(), 4);
+        assert_paragraph_with_single_text(&result[0], "===");
+        assert!(matches!(&result[1], LineToken::HorizontalRule(_)));
+        assert!(matches!(&result[2], LineToken::UnorderedListBlock(_)));
+        assert!(matches!(&result[3], LineToken::HorizontalRule(_)));
+    }
+
+    #[test]
+    fn test_lazy_line_is_not_setext_underline() {
+        let result = Tokenizer::tokenizer("> foo\nbar\n===");
+        assert_eq!(result.len(), 1);
+        if let LineToken::Quote(token) = &result[0] {
+            let inline_tokens = paragraph_tokens(&token.children);
+            assert_eq!(inline_tokens.len(), 5);
+            assert_text_token(&inline_tokens[4], "===");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_horizontal_rule_wins_over_list_item() {
+        let result = Tokenizer::tokenizer("- foo\n- - -\n* * *");
+        assert_eq!(result.len(), 3);
+        assert!(matches!(&result[0], LineToken::UnorderedListBlock(_)));
+        assert!(matches!(&result[1], LineToken::HorizontalRule(_)));
+        assert!(matches!(&result[2], LineToken::HorizontalRule(_)));
+    }
+
+    #[test]
+    fn test_tokenizer_with_multibyte_lines() {
+        let text = "# Überschrift\nétait *là*\n>引用\n- 項目\n1. 第一";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 5);
+        if let LineToken::HeaderToken(token) = &result[0] {
+            assert_text_token(&token.inline_tokens[0], "Überschrift");
+        } else {
+            panic!();
+        }
+        if let LineToken::Paragraph(token) = &result[1] {
+            assert_text_token(&token.inline_tokens[0], "était ");
+            assert_special_token_group(&token.inline_tokens[1], "là", '*');
+        } else {
+            panic!();
+        }
+        if let LineToken::Quote(token) = &result[2] {
+            assert_text_token(&paragraph_tokens(&token.children)[0], "引用");
+        } else {
+            panic!();
+        }
+        assert_unordered_lists(&result[3], vec!["項目"], '-');
+        assert_ordered_lists(&result[4], vec!["第一"], 1, '.');
+    }
+
+    #[test]
+    fn test_quote_with_nested_blocks() {
+        let text = "> # title\n> - first\n> - second\n>\n> ```\n> code\n> ```\n>> inner";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        if let LineToken::Quote(token) = &result[0] {
+            assert_eq!(token.children.len(), 4);
+            assert!(matches!(&token.children[0], LineToken::HeaderToken(_)));
+            assert_unordered_lists(&token.children[1], vec!["first", "second"], '-');
+            if let LineToken::CodeBlock(code) = &token.children[2] {
+                assert_eq!(code.text, "code");
+            } else {
+                panic!();
+            }
+            if let LineToken::Quote(inner) = &token.children[3] {
+                assert_text_token(&paragraph_tokens(&inner.children)[0], "inner");
+            } else {
+                panic!();
+            }
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_list_item_with_paragraph_and_code_block() {
+        let text = "- first\n\n  ```\n  code\n  ```\n- second\n\nafter";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 2);
+        if let LineToken::UnorderedListBlock(block) = &result[0] {
+            assert!(!block.tight);
+            assert_eq!(block.lists.len(), 2);
+            if let LineToken::UnorderedList(item) = &block.lists[0] {
+                assert_eq!(item.children.len(), 2);
+                assert_text_token(&paragraph_tokens(&item.children)[0], "first");
+                assert!(matches!(&item.children[1], LineToken::CodeBlock(_)));
+            } else {
+                panic!();
+            }
+        } else {
+            panic!();
+        }
+        assert_paragraph_with_single_text(&result[1], "after");
+    }
+
+    #[test]
+    fn test_list_item_with_quote() {
+        let text = "1. item\n   > quoted";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        if let LineToken::OrderedListBlock(block) = &result[0] {
+            assert!(block.tight);
+            if let LineToken::OrderedList(item) = &block.lists[0] {
+                assert_eq!(item.children.len(), 2);
+                assert!(matches!(&item.children[1], LineToken::Quote(_)));
+            } else {
+                panic!();
+            }
+        } else {
+            panic!();
+        }
+    }
+
+    fn first_item_children(token: &LineToken) -> &Vec<LineToken> {
+        match token {
+            LineToken::UnorderedListBlock(t) => match &t.lists[0] {
+                LineToken::UnorderedList(item) => &item.children,
+                _ => panic!(),
+            },
+            LineToken::OrderedListBlock(t) => match &t.lists[0] {
+                LineToken::OrderedList(item) => &item.children,
+                _ => panic!(),
+            },
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_indent_width_and_strip_indent() {
+        assert_eq!(LineToken::indent_width("abc"), 0);
+        assert_eq!(LineToken::indent_width("   abc"), 3);
+        assert_eq!(LineToken::indent_width("\tabc"), 4);
+        assert_eq!(LineToken::indent_width("  \tabc"), 4);
+        assert_eq!(LineToken::strip_indent("    abc", 2), Some("  abc"));
+        assert_eq!(LineToken::strip_indent("\tabc", 2), Some("abc"));
+        assert_eq!(LineToken::strip_indent(" abc", 2), None);
+        assert_eq!(LineToken::strip_indent("  ", 2), Some(""));
+    }
+
+    #[test]
+    fn test_three_level_nested_list() {
+        let text = "- one\n  - two\n    - three\n  - two again\n- one again";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        if let LineToken::UnorderedListBlock(block) = &result[0] {
+            assert_eq!(block.lists.len(), 2);
+        } else {
+            panic!();
+        }
+        let first = first_item_children(&result[0]);
+        assert_eq!(first.len(), 2);
+        assert_text_token(&paragraph_tokens(first)[0], "one");
+        if let LineToken::UnorderedListBlock(block) = &first[1] {
+            assert_eq!(block.lists.len(), 2);
+        } else {
+            panic!();
+        }
+        let second = first_item_children(&first[1]);
+        assert_text_token(&paragraph_tokens(second)[0], "two");
+        assert_unordered_lists(&second[1], vec!["three"], '-');
+    }
+
+    #[test]
+    fn test_mixed_ordered_and_unordered_nested_list() {
+        let text = "1. one\n   * two\n     1) three";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        let first = first_item_children(&result[0]);
+        assert_unordered_block_symbol(&first[1], '*');
+        let second = first_item_children(&first[1]);
+        assert_ordered_lists(&second[1], vec!["three"], 1, ')');
+    }
+
+    #[test]
+    fn test_list_items_indented_less_than_content_are_siblings() {
+        let text = "- a\n - b\n  - c\n   - d";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        assert_unordered_lists(&result[0], vec!["a", "b", "c", "d"], '-');
+    }
+
+    #[test]
+    fn test_tab_indented_nested_list() {
+        let text = "- one\n\t- two\n\t\t- three";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        let first = first_item_children(&result[0]);
+        let second = first_item_children(&first[1]);
+        assert_unordered_lists(&second[1], vec!["three"], '-');
+    }
+
+    #[test]
+    fn test_ordered_list_with_multi_digit_start() {
+        let text = "10. ten\n11. eleven";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        assert_ordered_lists(&result[0], vec!["ten", "eleven"], 10, '.');
+
+        let text = "123456789) nine digits";
+        let result = Tokenizer::tokenizer(text);
+        assert_ordered_lists(&result[0], vec!["nine digits"], 123456789, ')');
+    }
+
+    #[test]
+    fn test_ordered_list_with_too_many_digits() {
+        let text = "1234567890. ten digits";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 1);
+        assert_paragraph_with_single_text(&result[0], "1234567890. ten digits");
+    }
+
+    #[test]
+    fn test_ordered_list_delimiter_after_multiple_digits() {
+        let text = "10) first\n11. second";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 2);
+        assert_ordered_lists(&result[0], vec!["first"], 10, ')');
+        assert_ordered_lists(&result[1], vec!["second"], 11, '.');
+    }
+
+    fn assert_code_block(token: &LineToken, text: &str, info: Option<&str>) {
+        if let LineToken::CodeBlock(token) = token {
+            assert_eq!(token.text, text);
+            assert_eq!(token.info.as_deref(), info);
+        } else {
+            println!("{:?}", token);
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_fenced_code_block_with_info_string() {
+        let result = Tokenizer::tokenizer("```rust ignore\nfn main() {}\n```");
+        assert_eq!(result.len(), 1);
+        assert_code_block(&result[0], "fn main() {}", Some("rust ignore"));
+        if let LineToken::CodeBlock(token) = &result[0] {
+            assert_eq!(token.language(), Some("rust"));
+        }
+    }
+
+    #[test]
+    fn test_fenced_code_block_with_tildes() {
+        let result = Tokenizer::tokenizer("~~~ python\n```\nprint()\n~~~\nafter");
+        assert_eq!(result.len(), 2);
+        assert_code_block(&result[0], "```\nprint()", Some("python"));
+        assert_paragraph_with_single_text(&result[1], "after");
+    }
+
+    #[test]
+    fn test_fenced_code_block_fence_length() {
+        let result = Tokenizer::tokenizer("````\n```\ncode\n``````\nafter");
+        assert_eq!(result.len(), 2);
+        assert_code_block(&result[0], "```\ncode", None);
+        assert_paragraph_with_single_text(&result[1], "after");
+    }
+
+    #[test]
+    fn test_indented_fenced_code_block() {
+        let result = Tokenizer::tokenizer("  ```\n  a\n    b\nc\n   ```");
+        assert_eq!(result.len(), 1);
+        assert_code_block(&result[0], "a\n  b\nc", None);
+    }
+
+    #[test]
+    fn test_unclosed_fenced_code_block() {
+        let result = Tokenizer::tokenizer("~~~\ncode\n```");
+        assert_eq!(result.len(), 1);
+        assert_code_block(&result[0], "code\n```", None);
+    }
+
+    #[test]
+    fn test_backtick_fence_info_with_backtick() {
+        assert!(CodeBlock::tokenizer(&["``` a`b"], 0).is_none());
+        assert!(CodeBlock::tokenizer(&["~~~ a`b"], 0).is_some());
+        assert!(CodeBlock::tokenizer(&["``"], 0).is_none());
+    }
+
+    #[test]
+    fn test_indented_code_block() {
+        let result =
+            Tokenizer::tokenizer("    let a = *b * c_d_e;\n\tindented\n\n      two more\n\nafter");
+        assert_eq!(result.len(), 2);
+        assert_code_block(
+            &result[0],
+            "let a = *b * c_d_e;\nindented\n\n  two more",
+            None,
+        );
+        assert_paragraph_with_single_text(&result[1], "after");
+    }
+
+    #[test]
+    fn test_indented_code_block_cannot_interrupt_paragraph() {
+        let result = Tokenizer::tokenizer("text\n    more text\n\n    code");
+        assert_eq!(result.len(), 2);
+        if let LineToken::Paragraph(token) = &result[0] {
+            assert_eq!(token.inline_tokens.len(), 3);
+            assert_text_token(&token.inline_tokens[0], "text");
+            assert!(matches!(&token.inline_tokens[1], InlineToken::SoftBreak(_)));
+            assert_text_token(&token.inline_tokens[2], "more text");
+        } else {
+            panic!();
+        }
+        assert_code_block(&result[1], "code", None);
+    }
+
+    #[test]
+    fn test_paragraph_lines_are_joined() {
+        let result = Tokenizer::tokenizer("first line\n  second line\nthird line");
+        assert_eq!(result.len(), 1);
+        if let LineToken::Paragraph(token) = &result[0] {
+            assert_eq!(token.inline_tokens.len(), 5);
+            assert_text_token(&token.inline_tokens[0], "first line");
+            assert!(matches!(&token.inline_tokens[1], InlineToken::SoftBreak(_)));
+            assert_text_token(&token.inline_tokens[2], "second line");
+            assert!(matches!(&token.inline_tokens[3], InlineToken::SoftBreak(_)));
+            assert_text_token(&token.inline_tokens[4], "third line");
+        } else {
+            panic!();
+        }
+    }
+
+    #[test]
+    fn test_paragraph_interrupted_by_other_blocks() {
+        let text = "a\n# header\nb\n```\ncode\n```\nc\n***\nd\n> quote";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(result.len(), 8);
+        assert_paragraph_with_single_text(&result[0], "a");
+        assert!(matches!(&result[1], LineToken::HeaderToken(_)));
+        assert_paragraph_with_single_text(&result[2], "b");
+        assert_code_block(&result[3], "code", None);
+        assert_paragraph_with_single_text(&result[4], "c");
+        assert!(matches!(&result[5], LineToken::HorizontalRule(_)));
+        assert_paragraph_with_single_text(&result[6], "d");
+        assert!(matches!(&result[7], LineToken::Quote(_)));
+
+        let result = Tokenizer::tokenizer("a\n- item\n\nb\n1. item");
+        assert_eq!(result.len(), 4);
+        assert!(matches!(&result[1], LineToken::UnorderedListBlock(_)));
+        assert!(matches!(&result[3], LineToken::OrderedListBlock(_)));
+    }
+
+    #[test]
+    fn test_paragraph_not_interrupted_by_list_not_starting_at_one() {
+        let result = Tokenizer::tokenizer(
+            "The number of windows in my house is\n14.  The number of doors is 6.",
+        );
+        assert_eq!(result.len(), 1);
+        assert!(matches!(&result[0], LineToken::Paragraph(_)));
+
+        let result = Tokenizer::tokenizer("text\n*\nmore");
+        assert!(matches!(&result[0], LineToken::Paragraph(_)));
+    }
+
+    #[test]
+    fn test_lazy_continuation_in_list_item() {
+        let result = Tokenizer::tokenizer("- item\ncontinued");
+        assert_eq!(result.len(), 1);
+        let children = first_item_children(&result[0]);
+        let inline_tokens = paragraph_tokens(children);
+        assert_eq!(inline_tokens.len(), 3);
+        assert_text_token(&inline_tokens[2], "continued");
+    }
+
+    #[test]
+    fn test_indented_code_block_after_header_and_in_list() {
+        let result = Tokenizer::tokenizer("# title\n    code");
+        assert_eq!(result.len(), 2);
+        assert_code_block(&result[1], "code", None);
+
+        let result = Tokenizer::tokenizer("- item\n\n      code");
+        let children = first_item_children(&result[0]);
+        assert_eq!(children.len(), 2);
+        assert_code_block(&children[1], "code", None);
+    }
+
+    #[test]
+    fn test_list_block_errors() {
+        let paragraph = || {
+            LineToken::Paragraph(Paragraph {
+                inline_tokens: vec![],
+                span: Span::default(),
+            })
+        };
+        assert_eq!(
+            LineToken::new_list_block(paragraph()).unwrap_err(),
+            Error::NotAListItem
+        );
+        let item = LineToken::OrderedList(OrderedList::new(1, '.', vec![]));
+        let mut tokens = vec![LineToken::new_list_block(item).unwrap()];
+        assert_eq!(
+            LineToken::push_to_last_list_block(&mut tokens, paragraph()).unwrap_err(),
+            Error::NotAListItem
+        );
+        let item = LineToken::UnorderedList(UnorderedList::new('-', vec![]));
+        assert_eq!(
+            LineToken::push_to_last_list_block(&mut [], item).unwrap_err(),
+            Error::NotAListBlock
+        );
+    }
+
+    #[test]
+    fn test_nesting_is_limited() {
+        let text = ">".repeat(MAX_DEPTH + 10);
+        let mut tokens = Tokenizer::tokenizer(&text);
+        let mut depth = 0;
+        while let Some(LineToken::Quote(quote)) = tokens.pop() {
+            depth += 1;
+            tokens = quote.children;
+        }
+        assert_eq!(depth, MAX_DEPTH);
+
+        let text = format!("{}a", "- ".repeat(MAX_DEPTH + 10));
+        let mut tokens = Tokenizer::tokenizer(&text);
+        let mut depth = 0;
+        while let Some(LineToken::UnorderedListBlock(mut block)) = tokens.pop() {
+            depth += 1;
+            match block.lists.pop() {
+                Some(LineToken::UnorderedList(item)) => tokens = item.children,
+                _ => panic!(),
+            }
+        }
+        assert_eq!(depth, MAX_DEPTH);
+    }
+
+    fn source<'a>(text: &'a str, token: &LineToken) -> &'a str {
+        let span = token.span();
+        &text[span.start.offset..span.end.offset]
+    }
+
+    #[test]
+    fn test_block_spans() {
+        let text = "# a\n\n  b\n  c\n\n---\n```\nd\n```\n\n    e\n\nf\n===\n[g]: h";
+        let result = Tokenizer::tokenizer(text);
+        let sources: Vec<&str> = result.iter().map(|t| source(text, t)).collect();
+        assert_eq!(
+            sources,
+            vec![
+                "# a",
+                "b\n  c",
+                "---",
+                "```\nd\n```",
+                "    e",
+                "f\n===",
+                "[g]: h"
+            ]
+        );
+        let span = result[1].span();
+        assert_eq!(span.start, Position::new(7, 3, 3));
+        assert_eq!(span.end, Position::new(12, 4, 4));
+    }
+
+    #[test]
+    fn test_container_spans() {
+        let text = "> a *b*\nc\n\n- d\n  1. e\n- f";
+        let result = Tokenizer::tokenizer(text);
+        assert_eq!(source(text, &result[0]), "> a *b*\nc");
+        match &result[0] {
+            LineToken::Quote(quote) => {
+                assert_eq!(source(text, &quote.children[0]), "a *b*\nc");
+                let tokens = paragraph_tokens(&quote.children);
+                let span = tokens[1].span();
+                assert_eq!(&text[span.start.offset..span.end.offset], "*b*");
+                assert_eq!(span.start, Position::new(4, 1, 5));
+            }
+            _ => panic!(),
+        }
+        assert_eq!(source(text, &result[1]), "- d\n  1. e\n- f");
+        match &result[1] {
+            LineToken::UnorderedListBlock(block) => {
+                assert_eq!(source(text, &block.lists[0]), "- d\n  1. e");
+                assert_eq!(source(text, &block.lists[1]), "- f");
+                match &block.lists[0] {
+                    LineToken::UnorderedList(item) => {
+                        assert_eq!(source(text, &item.children[1]), "1. e");
+                        assert_eq!(item.children[1].span().start.column, 3);
+                    }
+                    _ => panic!(),
+                }
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[cfg(feature = "tables")]
+    fn table(text: &str) -> Table {
+        match Tokenizer::try_tokenize_with(text, Extensions::gfm())
+            .unwrap()
+            .remove(0)
+        {
+            LineToken::Table(table) => table,
+            token => panic!("expected a table, found {:?}", token),
+        }
+    }
+
+    #[cfg(feature = "tables")]
+    fn cell_text(cell: &TableCell) -> String {
+        cell.inline_tokens
+            .iter()
+            .map(|t| match t {
+                InlineToken::TextToken(t) => t.text.clone(),
+                InlineToken::SpecialToken(t) => format!("{}{:?}", t.token, t.inline_tokens.len()),
+                t => panic!("unexpected {:?}", t),
+            })
+            .collect()
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn test_table() {
+        let table = table("| a | b \\| c | `d | e` |\n|:--|:-:|--:|\n| 1 | 2 |\n3|4|5|6");
+        assert_eq!(
+            table.alignments,
+            vec![Alignment::Left, Alignment::Center, Alignment::Right]
+        );
+        let header: Vec<String> = table.header.cells.iter().map(cell_text).collect();
+        assert_eq!(header, vec!["a", "b | c", "`1"]);
+        assert_eq!(table.rows.len(), 2);
+        let rows: Vec<Vec<String>> = table
+            .rows
+            .iter()
+            .map(|row| row.cells.iter().map(cell_text).collect())
+            .collect();
+        assert_eq!(rows, vec![vec!["1", "2", ""], vec!["3", "4", "5"]]);
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn test_table_ends() {
+        let result = Tokenizer::try_tokenize_with("a\nb|c\n-|-\nd\n# e", Extensions::gfm());
+        let result = result.unwrap();
+        assert_eq!(result.len(), 3);
+        assert_paragraph_with_single_text(&result[0], "a");
+        match &result[1] {
+            LineToken::Table(t) => assert_eq!(t.rows.len(), 1),
+            t => panic!("expected a table, found {:?}", t),
+        }
+        assert!(matches!(result[2], LineToken::HeaderToken(_)));
+        let text = "a | b\n- | -";
+        let result = Tokenizer::try_tokenize_with(text, Extensions::gfm()).unwrap();
+        assert!(matches!(result[0], LineToken::Table(_)));
+    }
+
+    #[test]
+    fn test_not_a_table() {
+        for text in ["a|b\n-|-", "a\n---", "a|b\n-|-|-", "a|b\n-|x"] {
+            let result = Tokenizer::tokenizer(text);
+            assert!(!matches!(result[0], LineToken::Table(_)), "{}", text);
+            let result = Tokenizer::try_tokenize_with(text, Extensions::gfm()).unwrap();
+            let is_table = matches!(result[0], LineToken::Table(_));
+            assert_eq!(is_table, cfg!(feature = "tables") && text == "a|b\n-|-");
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "tables")]
+    fn test_table_spans() {
+        let text = "x\n\n | a\\|b | *c* |\n |---|---|";
+        let table = table(&text[3..]);
+        let span = table.header.cells[0].span;
+        assert_eq!((span.start.column, span.end.column), (4, 8));
+        let result = Tokenizer::try_tokenize_with(text, Extensions::gfm()).unwrap();
+        assert_eq!(source(text, &result[1]), "| a\\|b | *c* |\n |---|---|");
+        match &result[1] {
+            LineToken::Table(t) => {
+                let span = t.header.cells[1].inline_tokens[0].span();
+                assert_eq!(&text[span.start.offset..span.end.offset], "*c*");
+                let span = t.header.cells[0].span;
+                assert_eq!(&text[span.start.offset..span.end.offset], "a\\|b");
+            }
+            _ => panic!(),
+        }
+    }
+
+    #[test]
+    fn test_task_marker() {
+        let gfm = Extensions::gfm();
+        let expected = |checked: bool, rest| {
+            if cfg!(feature = "task-lists") {
+                Some((checked, rest))
+            } else {
+                None
+            }
+        };
+        assert_eq!(LineToken::task_marker("[ ] a", gfm), expected(false, "a"));
+        assert_eq!(LineToken::task_marker("[x]\t a", gfm), expected(true, "a"));
+        assert_eq!(LineToken::task_marker("[X] a", gfm), expected(true, "a"));
+        assert_eq!(LineToken::task_marker("[ ] a", Extensions::default()), None);
+        for text in ["[ ]", "[ ]  ", "[ ]a", "[y] a", "[  ] a", " [ ] a"] {
+            assert_eq!(LineToken::task_marker(text, gfm), None, "{}", text);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "task-lists")]
+    fn test_task_list_items() {
+        let text = "- [ ] a\n- [x] *b*\n- c\n\n1. [x] d";
+        let result = Tokenizer::try_tokenize_with(text, Extensions::gfm()).unwrap();
+        match &result[0] {
+            LineToken::UnorderedListBlock(block) => {
+                let checked: Vec<Option<bool>> = block
+                    .lists
+                    .iter()
+                    .map(|item| match item {
+                        LineToken::UnorderedList(t) => t.checked,
+                        _ => panic!(),
+                    })
+                    .collect();
+                assert_eq!(checked, vec![Some(false), Some(true), None]);
+                assert_eq!(source(text, &block.lists[1]), "- [x] *b*");
+                match &block.lists[1] {
+                    LineToken::UnorderedList(t) => {
+                        let span = paragraph_tokens(&t.children)[0].span();
+                        assert_eq!(&text[span.start.offset..span.end.offset], "*b*");
+                    }
+                    _ => panic!(),
+                }
+            }
+            t => panic!("expected a list, found {:?}", t),
+        }
+        match &result[1] {
+            LineToken::OrderedListBlock(block) => match &block.lists[0] {
+                LineToken::OrderedList(t) => assert_eq!(t.checked, Some(true)),
+                _ => panic!(),
+            },
+            t => panic!("expected a list, found {:?}", t),
+        }
+        match &Tokenizer::tokenizer("- [x] a")[0] {
+            LineToken::UnorderedListBlock(block) => match &block.lists[0] {
+                LineToken::UnorderedList(t) => assert_eq!(t.checked, None),
+                _ => panic!(),
+            },
+            t => panic!("expected a list, found {:?}", t),
+        }
     }
 }
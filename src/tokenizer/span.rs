@@ -0,0 +1,135 @@
+/// A point in the source text. `line` and `column` count from 1, and columns
+/// count bytes, like `offset`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Position {
+    pub offset: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    pub fn new(offset: usize, line: usize, column: usize) -> Self {
+        Self {
+            offset,
+            line,
+            column,
+        }
+    }
+}
+
+/// The source text a token was built from, from `start` up to but not
+/// including `end`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+
+    /// A span between two byte offsets, whose lines and columns are filled in
+    /// later.
+    pub fn from_offsets(start: usize, end: usize) -> Self {
+        Span::new(Position::new(start, 0, 0), Position::new(end, 0, 0))
+    }
+
+    /// A span between two lines and columns, whose offsets are filled in
+    /// later.
+    pub fn from_lines(
+        start_line: usize,
+        start_column: usize,
+        end_line: usize,
+        end_column: usize,
+    ) -> Self {
+        Span::new(
+            Position::new(0, start_line, start_column),
+            Position::new(0, end_line, end_column),
+        )
+    }
+
+    /// Whether the span covers no text, like that of a table cell the row
+    /// leaves out.
+    pub fn is_empty(&self) -> bool {
+        (self.end.line, self.end.column) <= (self.start.line, self.start.column)
+    }
+
+    pub fn map(&mut self, f: &dyn Fn(Position) -> Position) {
+        self.start = f(self.start);
+        self.end = f(self.end);
+    }
+}
+
+/// The offsets at which the lines of a text start, to convert between offsets
+/// and lines and columns.
+pub(crate) struct LineStarts {
+    starts: Vec<usize>,
+}
+
+impl LineStarts {
+    pub fn new(text: &str) -> Self {
+        let mut starts = vec![0];
+        starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+        Self { starts }
+    }
+
+    /// The starts of `lines` joined with newlines.
+    pub fn from_lines(lines: &[&str]) -> Self {
+        let mut starts = Vec::with_capacity(lines.len());
+        let mut start = 0;
+        for line in lines {
+            starts.push(start);
+            start += line.len() + 1;
+        }
+        Self { starts }
+    }
+
+    /// Fills in the line and column of a position from its offset.
+    pub fn position(&self, offset: usize) -> Position {
+        let line = self.starts.partition_point(|&s| s <= offset).max(1);
+        Position::new(offset, line, offset - self.starts[line - 1] + 1)
+    }
+
+    /// Fills in the offset of a position from its line and column.
+    pub fn offset(&self, position: Position) -> Position {
+        let start = match position.line.checked_sub(1) {
+            Some(line) => self.starts.get(line).copied().unwrap_or(0),
+            None => 0,
+        };
+        let offset = start + position.column.saturating_sub(1);
+        Position { offset, ..position }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_position_from_offset() {
+        let starts = LineStarts::new("ab\ncd\n\ne");
+        assert_eq!(starts.position(0), Position::new(0, 1, 1));
+        assert_eq!(starts.position(2), Position::new(2, 1, 3));
+        assert_eq!(starts.position(3), Position::new(3, 2, 1));
+        assert_eq!(starts.position(7), Position::new(7, 4, 1));
+        assert_eq!(starts.position(8), Position::new(8, 4, 2));
+    }
+
+    #[test]
+    fn test_empty_span() {
+        assert!(Span::from_lines(3, 3, 3, 3).is_empty());
+        assert!(!Span::from_lines(3, 2, 3, 3).is_empty());
+        assert!(!Span::from_lines(1, 5, 2, 1).is_empty());
+    }
+
+    #[test]
+    fn test_offset_from_position() {
+        let starts = LineStarts::from_lines(&["ab", "cd", "", "e"]);
+        assert_eq!(starts.offset(Position::new(0, 2, 2)).offset, 4);
+        assert_eq!(starts.offset(Position::new(0, 4, 1)).offset, 7);
+    }
+}
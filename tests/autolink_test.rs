@@ -0,0 +1,96 @@
+//! Autolinks between `<` and `>`, and GFM extended autolinks, rendered as
+//! HTML.
+extern crate mustdown;
+
+#[cfg(feature = "autolinks")]
+mod common;
+
+#[cfg(feature = "autolinks")]
+use common::{parse, parse_with};
+use mustdown::node::{Block, Document, Inline, Link};
+#[cfg(feature = "autolinks")]
+use mustdown::Extensions;
+use mustdown::Parser;
+
+#[test]
+fn test_autolink() {
+    assert_eq!(
+        Parser::new().parse("<https://a.com/b?c=d&e> <MAILTO:a@b.c> <a.b-c@d.org>"),
+        "<p>\n<a href=\"https://a.com/b?c=d&amp;e\">https://a.com/b?c=d&amp;e</a> \
+         <a href=\"MAILTO:a@b.c\">MAILTO:a@b.c</a> \
+         <a href=\"mailto:a.b-c@d.org\">a.b-c@d.org</a>\n</p>\n"
+    );
+    assert_eq!(
+        Parser::new().parse("<a b> <http://a b> \\<http://a.com>"),
+        "<p>\n&lt;a b&gt; &lt;http://a b&gt; \\&lt;http://a.com&gt;\n</p>\n"
+    );
+}
+
+#[test]
+fn test_autolink_node() {
+    let document = Document::parse("a <http://a.com>");
+    let link = match &document.children[0] {
+        Block::Paragraph(p) => &p.children[1],
+        b => panic!("{:?}", b),
+    };
+    match link {
+        Inline::Link(Link {
+            text,
+            destination,
+            reference,
+            span,
+        }) => {
+            assert_eq!(text, "http://a.com");
+            assert_eq!(destination, "http://a.com");
+            assert!(!reference);
+            assert_eq!((span.start.column, span.end.column), (3, 17));
+        }
+        i => panic!("{:?}", i),
+    }
+}
+
+#[test]
+#[cfg(feature = "autolinks")]
+fn test_extended_autolink() {
+    assert_eq!(
+        parse("Visit www.commonmark.org/help.\n(https://a.com/b_(c)) and x+y@z.org."),
+        "<p>\nVisit <a href=\"http://www.commonmark.org/help\">www.commonmark.org/help</a>.\n\
+         (<a href=\"https://a.com/b_(c)\">https://a.com/b_(c)</a>) and \
+         <a href=\"mailto:x+y@z.org\">x+y@z.org</a>.\n</p>\n"
+    );
+    assert_eq!(
+        parse("www.google.com/search?q=commonmark&hl;"),
+        "<p>\n<a href=\"http://www.google.com/search?q=commonmark\">\
+         www.google.com/search?q=commonmark</a>&amp;hl;\n</p>\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "autolinks")]
+fn test_extended_autolink_in_emphasis() {
+    assert_eq!(
+        parse("*www.a.com* _http://a.com_ **a@b.com**"),
+        "<p>\n<em><a href=\"http://www.a.com\">www.a.com</a></em> \
+         <em><a href=\"http://a.com\">http://a.com</a></em> \
+         <strong><a href=\"mailto:a@b.com\">a@b.com</a></strong>\n</p>\n"
+    );
+    assert_eq!(
+        parse("[www.a.com](b) `www.c.com`"),
+        "<p>\n<a href=\"b\">www.a.com</a> <code>www.c.com</code>\n</p>\n"
+    );
+}
+
+#[test]
+#[cfg(feature = "autolinks")]
+fn test_extended_autolink_is_an_extension() {
+    let text = "www.a.com http://a.com a@b.com";
+    assert_eq!(Parser::new().parse(text), format!("<p>\n{}\n</p>\n", text));
+    let extensions = Extensions {
+        autolinks: true,
+        ..Extensions::default()
+    };
+    assert_eq!(
+        parse_with(extensions, "www.a.com"),
+        "<p>\n<a href=\"http://www.a.com\">www.a.com</a>\n</p>\n"
+    );
+}
@@ -0,0 +1,30 @@
+//! Helpers shared by the tests of the GitHub Flavored Markdown extensions.
+// Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use mustdown::node::Document;
+use mustdown::{Event, Events, Extensions, Parser, ParserOptions};
+
+/// Renders `text` with every extension.
+pub fn parse(text: &str) -> String {
+    Parser::with_options(ParserOptions::gfm()).parse(text)
+}
+
+/// Renders `text` with only `extensions`.
+pub fn parse_with(extensions: Extensions, text: &str) -> String {
+    let options = ParserOptions {
+        extensions,
+        ..ParserOptions::default()
+    };
+    Parser::with_options(options).parse(text)
+}
+
+/// Parses `text` with every extension.
+pub fn document(text: &str) -> Document {
+    Document::try_parse_with(text, Extensions::gfm()).unwrap()
+}
+
+/// The events of `text` parsed with every extension.
+pub fn events(text: &str) -> Vec<Event> {
+    Events::new(document(text)).collect()
+}
@@ -0,0 +1,87 @@
+//! Inputs that try to get raw HTML into the output. Everything the user wrote
+//! must come out escaped, whichever output path renders it.
+extern crate mustdown;
+use mustdown::Parser;
+
+fn parse(text: &str) -> String {
+    let mut parser = Parser::new();
+    parser.parse(text)
+}
+
+#[test]
+fn test_text_is_escaped() {
+    assert_eq!(parse("a < b & c"), "<p>\na &lt; b &amp; c\n</p>\n");
+    assert_eq!(
+        parse("<script>alert(1)</script>"),
+        "<p>\n&lt;script&gt;alert(1)&lt;/script&gt;\n</p>\n"
+    );
+}
+
+#[test]
+fn test_text_in_containers_is_escaped() {
+    assert_eq!(
+        parse("# <h1>\n\n> <b>quote</b>\n\n- <i>item</i>"),
+        "<h1>&lt;h1&gt;</h1>
+<blockquote>
+<p>
+&lt;b&gt;quote&lt;/b&gt;
+</p>
+</blockquote>
+<ul>
+<li>&lt;i&gt;item&lt;/i&gt;</li>
+</ul>
+"
+    );
+}
+
+#[test]
+fn test_emphasis_and_code_span_are_escaped() {
+    assert_eq!(
+        parse("*<em>* **&amp;** `<code>`"),
+        "<p>\n<em>&lt;em&gt;</em> <strong>&amp;amp;</strong> <code>&lt;code&gt;</code>\n</p>\n"
+    );
+}
+
+#[test]
+fn test_code_block_is_escaped() {
+    assert_eq!(
+        parse("```\n</code></pre><script>\n```"),
+        "<pre><code>\n&lt;/code&gt;&lt;/pre&gt;&lt;script&gt;\n</code></pre>\n"
+    );
+    assert_eq!(
+        parse("    <div>&</div>"),
+        "<pre><code>\n&lt;div&gt;&amp;&lt;/div&gt;\n</code></pre>\n"
+    );
+}
+
+#[test]
+fn test_code_block_language_cannot_break_attribute() {
+    assert_eq!(
+        parse("```\"><script>\nx\n```"),
+        "<pre><code class=\"language-&quot;&gt;&lt;script&gt;\">\nx\n</code></pre>\n"
+    );
+}
+
+#[test]
+fn test_link_cannot_break_attribute() {
+    assert_eq!(
+        parse("[<b>x</b>](\" onmouseover=\"alert(1))"),
+        "<p>\n<a href=\"&quot; onmouseover=&quot;alert(1\">&lt;b&gt;x&lt;/b&gt;</a>)\n</p>\n"
+    );
+}
+
+#[test]
+fn test_image_cannot_break_attribute() {
+    assert_eq!(
+        parse("![\"><script>](x\"y)"),
+        "<p>\n<img src=\"x&quot;y\" alt=\"&quot;&gt;&lt;script&gt;\">\n</p>\n"
+    );
+}
+
+#[test]
+fn test_note_link_cannot_break_attribute() {
+    assert_eq!(
+        parse("[a][n] ![b][n]\n\n[n]:\"><script>"),
+        "<p>\n<a href=\"&quot;&gt;&lt;script&gt;\">a</a> <img src=\"&quot;&gt;&lt;script&gt;\" alt=\"b\">\n</p>\n\n"
+    );
+}
@@ -30,6 +30,7 @@ code block
 [image]:to_image
 Normal text **aha**, another *one*, `inline code`, and [link](to_link), and ![image](to_image).
 Don't forget [link][link] and ![image][image]
+
 [link]:to_link
 ";
     let mut parser = Parser::new();
@@ -48,20 +49,24 @@ Don't forget [link][link] and ![image][image]
 <li>list1</li>
 <li>list2</li>
 </ul>
-<ol start=\"1\">
+<ol>
 <li>list1</li>
 <li>list2</li>
 </ol>
-<ol start=\"1\">
+<ol>
 <li>list1</li>
 <li>list2</li>
 </ol>
-<blockquote><p>
+<blockquote>
+<p>
 this is a quote
-</p></blockquote>
-<blockquote><p>
-this is a quote  <br>another one
-</p></blockquote>
+</p>
+</blockquote>
+<blockquote>
+<p>
+this is a quote<br>another one
+</p>
+</blockquote>
 <pre><code>
 code block
 </code></pre>
@@ -70,8 +75,6 @@ code block
 
 <p>
 Normal text <strong>aha</strong>, another <em>one</em>, <code>inline code</code>, and <a href=\"to_link\">link</a>, and <img src=\"to_image\" alt=\"image\">.
-</p>
-<p>
 Don't forget <a href=\"to_link\">link</a> and <img src=\"to_image\" alt=\"image\">
 </p>
 
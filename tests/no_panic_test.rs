@@ -0,0 +1,102 @@
+//! No input string may make the tokenizer or the parser panic, with or
+//! without the GFM extensions. These tests feed them pathological documents
+//! and a stream of pseudo-random ones built from the characters that matter to
+//! Markdown. `spec_test` also fails if any spec example panics.
+extern crate mustdown;
+
+use mustdown::{Parser, ParserOptions};
+
+const PIECES: &[&str] = &[
+    "*", "**", "_", "__", "`", "```", "~~~", "[", "]", "(", ")", "!", "\\", ">", "#", "-", "+",
+    "1.", "2)", "=", "  ", "    ", "\t", "\n", "\n\n", " ", "a", "word", "é", "日本", "🎉", "[x]:",
+    "<", "&", "\"", "|", ":", "\r\n",
+];
+
+/// A small xorshift generator, so the inputs are the same on every run.
+struct Random(u64);
+
+impl Random {
+    fn next(&mut self) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0 as usize
+    }
+}
+
+fn check(text: &str) {
+    for options in [ParserOptions::default(), ParserOptions::gfm()] {
+        let mut parser = Parser::with_options(options);
+        parser.set_collect_diagnostics(true);
+        assert!(parser.try_parse(text).is_ok(), "{:?}", text);
+    }
+}
+
+#[test]
+fn test_random_documents() {
+    let mut random = Random(0x2545_f491_4f6c_dd1d);
+    for _ in 0..500 {
+        let len = random.next() % 40;
+        let text: String = (0..len)
+            .map(|_| PIECES[random.next() % PIECES.len()])
+            .collect();
+        check(&text);
+    }
+}
+
+#[test]
+fn test_deep_nesting() {
+    check(&">".repeat(10_000));
+    check(&"> ".repeat(1_000));
+    check(&format!("{}a", "- ".repeat(1_000)));
+    check(&format!("{}a", "1. ".repeat(1_000)));
+    check(&format!("{}a{}", "*".repeat(10_000), "*".repeat(10_000)));
+    check(&format!("{}a{}", "*a ".repeat(1_000), " a*".repeat(1_000)));
+    check(&"[".repeat(1_000));
+    check(&"![](".repeat(1_000));
+    check(&"`".repeat(10_000));
+}
+
+#[test]
+fn test_odd_documents() {
+    let texts = [
+        "",
+        "\n",
+        "\n\n\n",
+        " ",
+        "\t",
+        "\0",
+        "\r",
+        "\u{feff}",
+        "-",
+        "1.",
+        "#",
+        ">",
+        "```",
+        "    ",
+        "- \t",
+        "*\t*",
+        "1.\t\tx",
+        ">\t\tx",
+        "[]:",
+        "[]()",
+        "![]()",
+        "[a][]",
+        "\\",
+        "\\\n",
+        "a  \n",
+        "===",
+        "---",
+        "- ---",
+        "é\u{301}*x*",
+        "\t\u{3000}*x*",
+        "|\n|",
+        "|\n-|",
+        "\\|\n-|",
+        "`|\n-|-",
+        "é|\\\n-|-\n\\é",
+    ];
+    for text in &texts {
+        check(text);
+    }
+}
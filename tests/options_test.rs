@@ -0,0 +1,127 @@
+//! Renders the same document with every combination of `ParserOptions` and
+//! checks each option's effect on the HTML.
+extern crate mustdown;
+
+use mustdown::{Extensions, Parser, ParserOptions, SoftBreakStyle};
+
+const LINES: [&str; 7] = [
+    "a",
+    "b  ",
+    "c [l](javascript:x) ![i](data:text/html,x) ![j](data:image/png;base64,A)",
+    "",
+    "***",
+    "",
+    "~~d~~ www.e.f",
+];
+
+fn all_options() -> Vec<ParserOptions> {
+    let mut result = Vec::new();
+    for extensions in [Extensions::default(), Extensions::gfm()] {
+        for safe in [false, true] {
+            for xhtml in [false, true] {
+                for soft_break in [
+                    SoftBreakStyle::Newline,
+                    SoftBreakStyle::Space,
+                    SoftBreakStyle::Hard,
+                ] {
+                    for source_positions in [false, true] {
+                        result.push(ParserOptions {
+                            extensions,
+                            safe,
+                            xhtml,
+                            soft_break,
+                            source_positions,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+fn expected(options: &ParserOptions) -> String {
+    let void_end = if options.xhtml { " />" } else { ">" };
+    let soft_break = match options.soft_break {
+        SoftBreakStyle::Newline => "\n".to_string(),
+        SoftBreakStyle::Space => " ".to_string(),
+        SoftBreakStyle::Hard => format!("<br{}", void_end),
+    };
+    let (script, page) = if options.safe {
+        ("", "")
+    } else {
+        ("javascript:x", "data:text/html,x")
+    };
+    let (p, hr, last) = if options.source_positions {
+        (
+            format!(" data-sourcepos=\"1:1-3:{}\"", LINES[2].len()),
+            " data-sourcepos=\"5:1-5:3\"".to_string(),
+            format!(" data-sourcepos=\"7:1-7:{}\"", LINES[6].len()),
+        )
+    } else {
+        (String::new(), String::new(), String::new())
+    };
+    // Extensions are only read if their cargo feature is built.
+    let extensions = options.extensions;
+    let strikethrough = if cfg!(feature = "strikethrough") && extensions.strikethrough {
+        "<del>d</del>"
+    } else {
+        "~~d~~"
+    };
+    let autolink = if cfg!(feature = "autolinks") && extensions.autolinks {
+        "<a href=\"http://www.e.f\">www.e.f</a>"
+    } else {
+        "www.e.f"
+    };
+    format!(
+        "<p{p}>\na{soft_break}b<br{end}c <a href=\"{script}\">l</a> \
+         <img src=\"{page}\" alt=\"i\"{end} \
+         <img src=\"data:image/png;base64,A\" alt=\"j\"{end}\n</p>\n<hr{hr}{end}\n\
+         <p{last}>\n{strikethrough} {autolink}\n</p>\n",
+        p = p,
+        soft_break = soft_break,
+        end = void_end,
+        script = script,
+        page = page,
+        hr = hr,
+        last = last,
+        strikethrough = strikethrough,
+        autolink = autolink,
+    )
+}
+
+#[test]
+fn test_every_option_combination() {
+    let text = LINES.join("\n");
+    let options = all_options();
+    assert_eq!(options.len(), 48);
+    for options in options {
+        let result = Parser::with_options(options).parse(&text);
+        assert_eq!(result, expected(&options), "{:?}", options);
+        let commonmark = ParserOptions {
+            extensions: Extensions::default(),
+            ..options
+        };
+        if options.extensions == Extensions::gfm() && cfg!(feature = "gfm") {
+            assert_ne!(result, Parser::with_options(commonmark).parse(&text));
+        }
+    }
+}
+
+#[test]
+fn test_defaults_are_commonmark() {
+    let text = LINES.join("\n");
+    let options = ParserOptions::default();
+    assert_eq!(options.extensions, Extensions::default());
+    assert_eq!(Parser::new().parse(&text), expected(&options));
+    assert_eq!(ParserOptions::gfm().extensions, Extensions::gfm());
+}
+
+#[test]
+fn test_options_can_change_between_documents() {
+    let mut parser = Parser::new();
+    assert_eq!(parser.parse("***"), "<hr>\n");
+    parser.options_mut().xhtml = true;
+    assert_eq!(parser.parse("***"), "<hr />\n");
+    assert!(parser.options().xhtml);
+}
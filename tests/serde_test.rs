@@ -0,0 +1,35 @@
+//! Round trips of the node tree and the options through JSON.
+#![cfg(feature = "serde")]
+extern crate mustdown;
+extern crate serde_json;
+
+use mustdown::{Document, ParserOptions, SoftBreakStyle};
+
+#[test]
+fn test_document_round_trip() {
+    let document = Document::parse("# a\n\n> *b* [c](d)\n\n1. `e`\n2. f  \n   g");
+    let json = serde_json::to_string(&document).unwrap();
+    let result: Document = serde_json::from_str(&json).unwrap();
+    assert_eq!(result, document);
+}
+
+#[test]
+fn test_options_round_trip() {
+    let options = ParserOptions {
+        safe: true,
+        soft_break: SoftBreakStyle::Hard,
+        ..ParserOptions::gfm()
+    };
+    let json = serde_json::to_string(&options).unwrap();
+    let result: ParserOptions = serde_json::from_str(&json).unwrap();
+    assert_eq!(result, options);
+}
+
+#[test]
+fn test_missing_options_are_defaults() {
+    let result: ParserOptions =
+        serde_json::from_str(r#"{"xhtml": true, "extensions": {"tables": true}}"#).unwrap();
+    assert!(result.xhtml && result.extensions.tables);
+    assert!(!result.safe && !result.extensions.autolinks);
+    assert_eq!(result.soft_break, SoftBreakStyle::Newline);
+}
@@ -0,0 +1,84 @@
+//! Checks the spans of every token built from the CommonMark spec examples:
+//! each one must lie inside the source text and inside the span of the token
+//! that holds it, and its lines and columns must agree with its offsets.
+extern crate mustdown;
+extern crate serde_json;
+
+use mustdown::tokenizer::{Position, Span};
+use mustdown::{InlineToken, LineToken, Tokenizer};
+use std::fs;
+use std::path::PathBuf;
+
+fn load_examples() -> Vec<String> {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/spec.json");
+    let value: serde_json::Value =
+        serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| e["markdown"].as_str().unwrap().to_string())
+        .collect()
+}
+
+fn check_position(text: &str, position: Position) {
+    assert!(text.is_char_boundary(position.offset), "{:?}", position);
+    let before = &text[..position.offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+    assert_eq!((position.line, position.column), (line, column));
+}
+
+fn check_span(text: &str, span: Span, outer: Span) {
+    check_position(text, span.start);
+    check_position(text, span.end);
+    assert!(span.start.offset <= span.end.offset, "{:?}", span);
+    assert!(
+        outer.start.offset <= span.start.offset && span.end.offset <= outer.end.offset,
+        "{:?} is outside {:?}",
+        span,
+        outer
+    );
+}
+
+fn check_inline(text: &str, token: &InlineToken, outer: Span) {
+    check_span(text, token.span(), outer);
+    let children = match token {
+        InlineToken::SpecialToken(t) => &t.inline_tokens,
+        InlineToken::DoubleSpecialToken(t) => &t.inline_tokens,
+        _ => return,
+    };
+    for child in children {
+        check_inline(text, child, token.span());
+    }
+}
+
+fn check_line(text: &str, token: &LineToken, outer: Span) {
+    check_span(text, token.span(), outer);
+    let (children, inline_tokens) = match token {
+        LineToken::HeaderToken(t) => (None, Some(&t.inline_tokens)),
+        LineToken::Paragraph(t) => (None, Some(&t.inline_tokens)),
+        LineToken::Quote(t) => (Some(&t.children), None),
+        LineToken::OrderedListBlock(t) => (Some(&t.lists), None),
+        LineToken::UnorderedListBlock(t) => (Some(&t.lists), None),
+        LineToken::OrderedList(t) => (Some(&t.children), None),
+        LineToken::UnorderedList(t) => (Some(&t.children), None),
+        _ => (None, None),
+    };
+    for child in children.into_iter().flatten() {
+        check_line(text, child, token.span());
+    }
+    for child in inline_tokens.into_iter().flatten() {
+        check_inline(text, child, token.span());
+    }
+}
+
+#[test]
+fn test_spans_of_spec_examples() {
+    for text in load_examples() {
+        let whole = Span::new(Position::new(0, 1, 1), Position::new(text.len(), 0, 0));
+        for token in Tokenizer::tokenizer(&text) {
+            check_line(&text, &token, whole);
+        }
+    }
+}
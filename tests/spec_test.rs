@@ -0,0 +1,210 @@
+//! Runs the CommonMark spec examples in `tests/fixtures/spec.json` through
+//! `Parser::parse` and reports the results per spec section.
+//!
+//! Examples listed in `tests/fixtures/spec_known_failures.txt` are expected to
+//! fail. The test fails when any other example fails (a regression) or when a
+//! listed example starts passing, so the list always matches the current
+//! state. Run with `UPDATE_KNOWN_FAILURES=1` to rewrite the list, and with
+//! `-- --nocapture` to see the per-section report.
+extern crate mustdown;
+extern crate regex;
+extern crate serde_json;
+
+use mustdown::Parser;
+use regex::Regex;
+use std::collections::BTreeSet;
+use std::fs;
+use std::panic;
+use std::path::PathBuf;
+
+const BLOCK_TAGS: &[&str] = &[
+    "blockquote",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+struct Example {
+    number: u64,
+    section: String,
+    markdown: String,
+    html: String,
+}
+
+fn fixture_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("fixtures")
+        .join(name)
+}
+
+fn load_examples() -> Vec<Example> {
+    let text = fs::read_to_string(fixture_path("spec.json")).unwrap();
+    let value: serde_json::Value = serde_json::from_str(&text).unwrap();
+    value
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| Example {
+            number: e["example"].as_u64().unwrap(),
+            section: e["section"].as_str().unwrap().to_string(),
+            markdown: e["markdown"].as_str().unwrap().to_string(),
+            html: e["html"].as_str().unwrap().to_string(),
+        })
+        .collect()
+}
+
+fn load_known_failures() -> BTreeSet<u64> {
+    let text = fs::read_to_string(fixture_path("spec_known_failures.txt")).unwrap_or_default();
+    text.lines()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(|l| l.parse().unwrap())
+        .collect()
+}
+
+fn write_known_failures(failures: &BTreeSet<u64>) {
+    let mut text = String::from(
+        "# CommonMark spec examples that mustdown does not pass yet.\n\
+         # Regenerate with `UPDATE_KNOWN_FAILURES=1 cargo test --test spec_test`.\n",
+    );
+    for number in failures {
+        text.push_str(&format!("{}\n", number));
+    }
+    fs::write(fixture_path("spec_known_failures.txt"), text).unwrap();
+}
+
+/// Removes differences that don't change the meaning of the HTML: whitespace
+/// next to block-level tags, the newlines just inside `<pre><code>`, the
+/// self-closing slash on void elements and whether `"` is escaped.
+fn normalize(html: &str) -> String {
+    let html = html.replace("&quot;", "\"");
+    let void = Regex::new(r"<(br|hr|img|input)([^>]*?)\s*/>").unwrap();
+    let html = void.replace_all(&html, "<$1$2>");
+    let code_start = Regex::new(r"(<pre><code[^>]*>)\n").unwrap();
+    let html = code_start.replace_all(&html, "$1");
+    let html = html.replace("\n</code></pre>", "</code></pre>");
+
+    let tag = Regex::new(r"</?([a-zA-Z][a-zA-Z0-9]*)[^>]*>").unwrap();
+    let mut result = String::new();
+    let mut last = 0;
+    let mut after_block = true;
+    for mat in tag.captures_iter(&html) {
+        let whole = mat.get(0).unwrap();
+        let text = &html[last..whole.start()];
+        let is_block = BLOCK_TAGS.contains(&mat[1].to_lowercase().as_str());
+        let text = if after_block { text.trim_start() } else { text };
+        let text = if is_block { text.trim_end() } else { text };
+        result.push_str(text);
+        result.push_str(whole.as_str());
+        after_block = is_block;
+        last = whole.end();
+    }
+    let text = &html[last..];
+    let text = if after_block { text.trim_start() } else { text };
+    result.push_str(text.trim_end());
+    result
+}
+
+/// Whether the example passes, or `None` if parsing it panicked.
+fn passes(example: &Example) -> Option<bool> {
+    let result = panic::catch_unwind(|| {
+        let mut parser = Parser::new();
+        parser.parse(&example.markdown)
+    });
+    result
+        .ok()
+        .map(|html| normalize(&html) == normalize(&example.html))
+}
+
+#[test]
+fn test_normalize() {
+    assert_eq!(normalize("<p>\nfoo\n</p>\n"), "<p>foo</p>");
+    assert_eq!(
+        normalize("<pre><code>\nfoo\n</code></pre>\n<hr />\n"),
+        "<pre><code>foo</code></pre><hr>"
+    );
+    assert_eq!(normalize("<p>a <em>b</em> c</p>"), "<p>a <em>b</em> c</p>");
+    assert_eq!(normalize("<p>&quot;a&quot;</p>"), "<p>\"a\"</p>");
+}
+
+#[test]
+fn test_commonmark_spec() {
+    let examples = load_examples();
+    let known_failures = load_known_failures();
+
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let results: Vec<(&Example, Option<bool>)> = examples.iter().map(|e| (e, passes(e))).collect();
+    panic::set_hook(hook);
+
+    let panicked: Vec<u64> = results
+        .iter()
+        .filter(|(_, passed)| passed.is_none())
+        .map(|(e, _)| e.number)
+        .collect();
+    assert!(
+        panicked.is_empty(),
+        "spec examples panicked: {:?}",
+        panicked
+    );
+
+    let mut sections: Vec<(&str, usize, usize)> = Vec::new();
+    let mut failures = BTreeSet::new();
+    for (example, passed) in &results {
+        match sections.last_mut() {
+            Some(s) if s.0 == example.section => {}
+            _ => sections.push((&example.section, 0, 0)),
+        }
+        let section = sections.last_mut().unwrap();
+        section.2 += 1;
+        if *passed == Some(true) {
+            section.1 += 1;
+        } else {
+            failures.insert(example.number);
+        }
+    }
+
+    for (name, passed, total) in &sections {
+        println!("{:<45} {:>3}/{:<3}", name, passed, total);
+    }
+    println!(
+        "{:<45} {:>3}/{:<3}",
+        "Total",
+        examples.len() - failures.len(),
+        examples.len()
+    );
+
+    if std::env::var_os("UPDATE_KNOWN_FAILURES").is_some() {
+        write_known_failures(&failures);
+        return;
+    }
+
+    let regressions: Vec<&u64> = failures.difference(&known_failures).collect();
+    let fixed: Vec<&u64> = known_failures.difference(&failures).collect();
+    assert!(
+        regressions.is_empty(),
+        "spec examples regressed: {:?}",
+        regressions
+    );
+    assert!(
+        fixed.is_empty(),
+        "spec examples now pass, remove them from spec_known_failures.txt: {:?}",
+        fixed
+    );
+}
@@ -0,0 +1,52 @@
+//! GFM strikethrough, rendered as HTML and walked as events.
+#![cfg(feature = "strikethrough")]
+extern crate mustdown;
+
+mod common;
+
+use common::{events, parse, parse_with};
+use mustdown::{Event, Extensions, Parser, Tag};
+
+#[test]
+fn test_strikethrough() {
+    assert_eq!(
+        parse("~~a~~ ~b~ ~~~c~~~ ~d~~"),
+        "<p>\n<del>a</del> <del>b</del> ~~~c~~~ ~d~~\n</p>\n"
+    );
+    assert_eq!(parse("~~a\nb~~"), "<p>\n<del>a\nb</del>\n</p>\n");
+    assert_eq!(parse("`~~a~~`"), "<p>\n<code>~~a~~</code>\n</p>\n");
+}
+
+#[test]
+fn test_strikethrough_nests_in_emphasis() {
+    assert_eq!(
+        parse("*~~a~~* **~b~** ~~*c* __d__~~"),
+        "<p>\n<em><del>a</del></em> <strong><del>b</del></strong> \
+         <del><em>c</em> <strong>d</strong></del>\n</p>\n"
+    );
+    assert_eq!(parse("# ~~a~~"), "<h1><del>a</del></h1>\n");
+}
+
+#[test]
+fn test_strikethrough_is_an_extension() {
+    assert_eq!(Parser::new().parse("~~a~~"), "<p>\n~~a~~\n</p>\n");
+    let extensions = Extensions {
+        strikethrough: true,
+        ..Extensions::default()
+    };
+    assert_eq!(parse_with(extensions, "~~a~~"), "<p>\n<del>a</del>\n</p>\n");
+}
+
+#[test]
+fn test_strikethrough_events() {
+    assert_eq!(
+        events("~a~"),
+        vec![
+            Event::Start(Tag::Paragraph),
+            Event::Start(Tag::Strikethrough),
+            Event::Text("a".to_string()),
+            Event::End(Tag::Strikethrough),
+            Event::End(Tag::Paragraph),
+        ]
+    );
+}
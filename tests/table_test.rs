@@ -0,0 +1,128 @@
+//! GFM tables, rendered as HTML and walked as events.
+#![cfg(feature = "tables")]
+extern crate mustdown;
+
+mod common;
+
+use common::{events, parse, parse_with};
+use mustdown::tokenizer::Alignment;
+use mustdown::{Event, Extensions, Parser, ParserOptions, Tag};
+
+#[test]
+fn test_table() {
+    let text = "| a | b \\| c | `d | e` |\n|:--|:-:|--:|\n| 1 | **2** |\n3 | 4 | 5 | 6";
+    assert_eq!(
+        parse(text),
+        "<table>
+<thead>
+<tr>
+<th align=\"left\">a</th>
+<th align=\"center\">b | c</th>
+<th align=\"right\"><code>d | e</code></th>
+</tr>
+</thead>
+<tbody>
+<tr>
+<td align=\"left\">1</td>
+<td align=\"center\"><strong>2</strong></td>
+<td align=\"right\"></td>
+</tr>
+<tr>
+<td align=\"left\">3</td>
+<td align=\"center\">4</td>
+<td align=\"right\">5</td>
+</tr>
+</tbody>
+</table>
+"
+    );
+}
+
+#[test]
+fn test_table_without_body() {
+    assert_eq!(
+        parse("a | b\n--|--"),
+        "<table>\n<thead>\n<tr>\n<th>a</th>\n<th>b</th>\n</tr>\n</thead>\n</table>\n"
+    );
+}
+
+#[test]
+fn test_table_between_blocks() {
+    let text = "x\na\n-\n\n> a\n> -|\n> b\n\nc";
+    assert_eq!(
+        parse(text),
+        "<h2>x\na</h2>\n<blockquote>\n<table>\n<thead>\n<tr>\n<th>a</th>\n</tr>\n</thead>\n\
+         <tbody>\n<tr>\n<td>b</td>\n</tr>\n</tbody>\n</table>\n</blockquote>\n<p>\nc\n</p>\n"
+    );
+    let text = "x\na|b\n-|-\n\n- a|b\n  -|-";
+    let result = parse(text);
+    assert!(result.starts_with("<p>\nx\n</p>\n<table>"), "{}", result);
+    assert!(result.contains("<li>\n<table>"), "{}", result);
+}
+
+#[test]
+fn test_tables_are_an_extension() {
+    let text = "a | b\n--|--";
+    assert_eq!(Parser::new().parse(text), "<p>\na | b\n--|--\n</p>\n");
+    let extensions = Extensions {
+        tables: true,
+        ..Extensions::default()
+    };
+    assert_eq!(parse_with(extensions, text), parse(text));
+}
+
+#[test]
+fn test_table_source_positions() {
+    let mut parser = Parser::with_options(ParserOptions::gfm());
+    parser.set_source_positions(true);
+    assert_eq!(
+        parser.parse(" a | b\n---|---\n c\n"),
+        "<table data-sourcepos=\"1:2-3:2\">
+<thead>
+<tr data-sourcepos=\"1:2-1:6\">
+<th data-sourcepos=\"1:2-1:2\">a</th>
+<th data-sourcepos=\"1:6-1:6\">b</th>
+</tr>
+</thead>
+<tbody>
+<tr data-sourcepos=\"3:2-3:2\">
+<td data-sourcepos=\"3:2-3:2\">c</td>
+<td></td>
+</tr>
+</tbody>
+</table>
+"
+    );
+}
+
+#[test]
+fn test_table_events() {
+    let alignments = vec![Alignment::Left, Alignment::None];
+    assert_eq!(
+        events("a|b\n:-|-\nc|d"),
+        vec![
+            Event::Start(Tag::Table(alignments.clone())),
+            Event::Start(Tag::TableHead),
+            Event::Start(Tag::TableRow),
+            Event::Start(Tag::HeaderCell(Alignment::Left)),
+            Event::Text("a".to_string()),
+            Event::End(Tag::HeaderCell(Alignment::Left)),
+            Event::Start(Tag::HeaderCell(Alignment::None)),
+            Event::Text("b".to_string()),
+            Event::End(Tag::HeaderCell(Alignment::None)),
+            Event::End(Tag::TableRow),
+            Event::End(Tag::TableHead),
+            Event::Start(Tag::TableBody),
+            Event::Start(Tag::TableRow),
+            Event::Start(Tag::TableCell(Alignment::Left)),
+            Event::Text("c".to_string()),
+            Event::End(Tag::TableCell(Alignment::Left)),
+            Event::Start(Tag::TableCell(Alignment::None)),
+            Event::Text("d".to_string()),
+            Event::End(Tag::TableCell(Alignment::None)),
+            Event::End(Tag::TableRow),
+            Event::End(Tag::TableBody),
+            Event::End(Tag::Table(alignments)),
+        ]
+    );
+}
@@ -0,0 +1,111 @@
+//! GFM task list items, rendered as HTML and walked as events.
+#![cfg(feature = "task-lists")]
+extern crate mustdown;
+
+mod common;
+
+use common::{document, events, parse};
+use mustdown::node::TaskCount;
+use mustdown::{Event, Events, Parser, ParserOptions, Tag};
+
+#[test]
+fn test_tight_task_list() {
+    assert_eq!(
+        parse("- [ ] a\n- [x] *b*\n- c"),
+        "<ul>
+<li><input type=\"checkbox\" disabled=\"\"> a</li>
+<li><input type=\"checkbox\" checked=\"\" disabled=\"\"> <em>b</em></li>
+<li>c</li>
+</ul>
+"
+    );
+}
+
+#[test]
+fn test_loose_task_list() {
+    assert_eq!(
+        parse("1. [X] a\n\n2. [ ] [b](c)"),
+        "<ol>
+<li>
+<p>
+<input type=\"checkbox\" checked=\"\" disabled=\"\"> a
+</p>
+</li>
+<li>
+<p>
+<input type=\"checkbox\" disabled=\"\"> <a href=\"c\">b</a>
+</p>
+</li>
+</ol>
+"
+    );
+}
+
+#[test]
+fn test_task_list_xhtml() {
+    let options = ParserOptions {
+        xhtml: true,
+        ..ParserOptions::gfm()
+    };
+    assert_eq!(
+        Parser::with_options(options).parse("- [x] a"),
+        "<ul>\n<li><input type=\"checkbox\" checked=\"\" disabled=\"\" /> a</li>\n</ul>\n"
+    );
+}
+
+#[test]
+fn test_task_lists_are_an_extension() {
+    assert_eq!(
+        Parser::new().parse("- [x] a"),
+        "<ul>\n<li>[x] a</li>\n</ul>\n"
+    );
+    assert_eq!(parse("[x] a"), "<p>\n[x] a\n</p>\n");
+}
+
+#[test]
+fn test_task_list_events() {
+    assert_eq!(
+        events("- [x] a\n- b"),
+        vec![
+            Event::Start(Tag::List(None)),
+            Event::Start(Tag::Item),
+            Event::TaskListMarker(true),
+            Event::Text("a".to_string()),
+            Event::End(Tag::Item),
+            Event::Start(Tag::Item),
+            Event::Text("b".to_string()),
+            Event::End(Tag::Item),
+            Event::End(Tag::List(None)),
+        ]
+    );
+    assert_eq!(
+        events("- [ ] a\n\n  b")[..5],
+        [
+            Event::Start(Tag::List(None)),
+            Event::Start(Tag::Item),
+            Event::Start(Tag::Paragraph),
+            Event::TaskListMarker(false),
+            Event::Text("a".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_task_list_html_of_events() {
+    let text = "- [x] a\n- [ ] b\n\n  c\n\n- [ ] ```\n  d\n  ```";
+    let mut output = String::new();
+    mustdown::html::push_html(&mut output, Events::new(document(text)));
+    assert_eq!(output, parse(text));
+}
+
+#[test]
+fn test_count_completed_tasks() {
+    let text = "# Release\n\n- [x] tag\n- [ ] publish\n  - [x] docs.rs\n- notes";
+    assert_eq!(
+        document(text).task_count(),
+        TaskCount {
+            completed: 2,
+            total: 3,
+        }
+    );
+}